@@ -0,0 +1,263 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::content::model;
+
+const MAGIC: &[u8; 8] = b"PORTCUL1";
+const HEADER_LEN: usize = 24; // Magic, payload length, payload hash
+
+/// Encoded tile artwork on disk, keyed by `masterId` and the requested width.
+///
+/// Each entry carries a header with the payload length and hash so truncated or
+/// corrupted files are detected and dropped instead of shown. The least recently
+/// used entries (by modification time, bumped on every hit) are evicted once the
+/// cache grows past `max_bytes`.
+pub struct ArtworkCache
+{
+    dir: PathBuf,
+    max_bytes: u64,
+    size: Mutex<u64>, // Bytes currently on disk
+}
+
+impl ArtworkCache
+{
+    /// `$XDG_CACHE_HOME/portcullis/artwork` or the platform equivalent.
+    pub fn default_dir() -> Option<PathBuf>
+    {
+        Some(dirs::cache_dir()?.join("portcullis").join("artwork"))
+    }
+
+    pub fn open(dir: PathBuf, max_bytes: u64) -> io::Result<Self>
+    {
+        fs::create_dir_all(&dir)?;
+
+        let cache = Self { dir, max_bytes, size: Mutex::new(0) };
+        let mut size = cache.size.lock().unwrap();
+        *size = cache.entries()?.iter().map(|entry| entry.1).sum();
+        cache.evict(&mut size)?;
+        drop(size);
+
+        Ok(cache)
+    }
+
+    pub fn get(&self, tile: &model::ImageDetails) -> Option<Vec<u8>>
+    {
+        let path = self.path(tile);
+        let entry = fs::read(&path).ok()?;
+
+        match decode_entry(&entry)
+        {
+            Some(payload) =>
+            {
+                // Bump the modification time so eviction sees this entry as recently used
+                let _ = File::options().write(true).open(&path).and_then(|file| file.set_modified(SystemTime::now()));
+                Some(payload.to_vec())
+            }
+
+            None =>
+            {
+                self.remove(tile);
+                None
+            }
+        }
+    }
+
+    pub fn dir(&self) -> &Path
+    {
+        &self.dir
+    }
+
+    pub fn put(&self, tile: &model::ImageDetails, payload: &[u8]) -> io::Result<()>
+    {
+        let path = self.path(tile);
+        let entry = encode_entry(payload);
+        let replaced = fs::metadata(&path).map_or(0, |metadata| metadata.len());
+
+        write_entry(&path, &entry)?;
+
+        let mut size = self.size.lock().unwrap();
+        *size = size.saturating_sub(replaced) + entry.len() as u64;
+        self.evict(&mut size)
+    }
+
+    pub fn remove(&self, tile: &model::ImageDetails)
+    {
+        let path = self.path(tile);
+
+        if let Ok(metadata) = fs::metadata(&path)
+        {
+            if fs::remove_file(&path).is_ok()
+            {
+                let mut size = self.size.lock().unwrap();
+                *size = size.saturating_sub(metadata.len());
+            }
+        }
+    }
+
+    fn path(&self, tile: &model::ImageDetails) -> PathBuf
+    {
+        let master_id: String = tile.master_id.chars().filter(char::is_ascii_alphanumeric).collect();
+        self.dir.join(format!("{}-{}.img", master_id, requested_width(&tile.url)))
+    }
+
+    fn entries(&self) -> io::Result<Vec<(PathBuf, u64, SystemTime)>>
+    {
+        let mut entries = Vec::new();
+
+        for entry in fs::read_dir(&self.dir)?
+        {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+
+            if metadata.is_file() && entry.path().extension().is_some_and(|extension| extension == "img")
+            {
+                entries.push((entry.path(), metadata.len(), metadata.modified()?));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn evict(&self, size: &mut u64) -> io::Result<()>
+    {
+        if *size <= self.max_bytes
+        {
+            return Ok(());
+        }
+
+        let mut entries = self.entries()?;
+        entries.sort_unstable_by_key(|entry| entry.2);
+
+        for (path, len, _) in entries
+        {
+            if *size <= self.max_bytes
+            {
+                break;
+            }
+
+            fs::remove_file(&path)?;
+            *size = size.saturating_sub(len);
+        }
+
+        Ok(())
+    }
+}
+
+/// The `width` query parameter of an artwork URL, 0 when absent.
+fn requested_width(url: &str) -> u32
+{
+    let query = url.split_once('?').map_or("", |(_, query)| query);
+
+    query
+        .split('&')
+        .find_map(|parameter| parameter.strip_prefix("width="))
+        .and_then(|width| width.parse().ok())
+        .unwrap_or(0)
+}
+
+pub(crate) fn encode_entry(payload: &[u8]) -> Vec<u8>
+{
+    let mut entry = Vec::with_capacity(HEADER_LEN + payload.len());
+    entry.extend_from_slice(MAGIC);
+    entry.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    entry.extend_from_slice(&fnv1a(payload).to_le_bytes());
+    entry.extend_from_slice(payload);
+    entry
+}
+
+pub(crate) fn decode_entry(entry: &[u8]) -> Option<&[u8]>
+{
+    if entry.len() < HEADER_LEN || &entry[.. 8] != MAGIC
+    {
+        return None;
+    }
+
+    let len = u64::from_le_bytes(entry[8 .. 16].try_into().ok()?);
+    let hash = u64::from_le_bytes(entry[16 .. 24].try_into().ok()?);
+    let payload = &entry[HEADER_LEN ..];
+
+    (payload.len() as u64 == len && fnv1a(payload) == hash).then_some(payload)
+}
+
+pub(crate) fn write_entry(path: &Path, entry: &[u8]) -> io::Result<()>
+{
+    let temporary = path.with_extension("tmp");
+
+    // Write then rename so a crash never leaves a half-written entry behind
+    File::create(&temporary)?.write_all(entry)?;
+    fs::rename(&temporary, path)
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64
+{
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::test_support::tile;
+
+    fn temporary_dir(name: &str) -> PathBuf
+    {
+        std::env::temp_dir().join(format!("portcullis-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn round_trip_and_corruption()
+    {
+        let dir = temporary_dir("cache-round-trip");
+        let cache = ArtworkCache::open(dir.clone(), 1 << 20).unwrap();
+
+        assert!(cache.get(&tile("http://localhost", "a")).is_none());
+
+        cache.put(&tile("http://localhost", "a"), b"artwork").unwrap();
+        assert_eq!(cache.get(&tile("http://localhost", "a")).unwrap(), b"artwork");
+        assert!(dir.join("a-500.img").exists());
+
+        // Flip a payload byte, the entry must be rejected and removed
+        let mut entry = fs::read(dir.join("a-500.img")).unwrap();
+        *entry.last_mut().unwrap() ^= 0xff;
+        fs::write(dir.join("a-500.img"), entry).unwrap();
+
+        assert!(cache.get(&tile("http://localhost", "a")).is_none());
+        assert!(!dir.join("a-500.img").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn evicts_least_recently_used()
+    {
+        let dir = temporary_dir("cache-eviction");
+        let entry_len = (HEADER_LEN + 100) as u64;
+        let cache = ArtworkCache::open(dir.clone(), entry_len * 2).unwrap();
+
+        cache.put(&tile("http://localhost", "a"), &[0; 100]).unwrap();
+        cache.put(&tile("http://localhost", "b"), &[0; 100]).unwrap();
+        assert!(cache.get(&tile("http://localhost", "a")).is_some());
+        cache.put(&tile("http://localhost", "c"), &[0; 100]).unwrap();
+
+        assert!(cache.get(&tile("http://localhost", "a")).is_some());
+        assert!(cache.get(&tile("http://localhost", "b")).is_none());
+        assert!(cache.get(&tile("http://localhost", "c")).is_some());
+
+        // Reopening with a smaller limit trims the cache right away
+        drop(cache);
+        let cache = ArtworkCache::open(dir.clone(), entry_len).unwrap();
+        assert_eq!(*cache.size.lock().unwrap(), entry_len);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn width_comes_from_the_url()
+    {
+        assert_eq!(requested_width("https://example.com/x/scale?format=jpeg&width=500&quality=90"), 500);
+        assert_eq!(requested_width("https://example.com/x/scale"), 0);
+    }
+}
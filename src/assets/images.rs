@@ -0,0 +1,475 @@
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+
+use crate::assets::cache::ArtworkCache;
+use crate::content::error::ContentError;
+use crate::content::model;
+use crate::content::source::{ContentSource, HttpClient};
+
+pub type ImageResult = (model::ImageDetails, Result<image::RgbaImage, ContentError>);
+
+/// Which tiles to download first. Lower is more urgent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority
+{
+    Focused, // The selected tile
+    Visible, // Anywhere on screen
+    Ahead,   // One screen ahead in the direction of travel
+}
+
+/// How often and how soon artwork that failed with a transient error is
+/// downloaded again.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy
+{
+    pub max_attempts: u32,
+    pub base_delay: Duration, // Before the second attempt, doubled after every failure
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy
+{
+    fn default() -> Self
+    {
+        Self { max_attempts: 5, base_delay: Duration::from_millis(500), max_delay: Duration::from_secs(30) }
+    }
+}
+
+impl RetryPolicy
+{
+    /// The wait after `attempts` failures. `jitter` in `0.0 ..= 1.0` picks a
+    /// point in the upper half of the exponential delay, so tiles that failed
+    /// together don't all come back at once.
+    pub fn delay(&self, attempts: u32, jitter: f32) -> Duration
+    {
+        let exponential = self.base_delay.saturating_mul(1 << attempts.saturating_sub(1).min(16));
+        let delay = exponential.min(self.max_delay);
+
+        delay.mul_f32(0.5 + jitter.clamp(0.0, 1.0) * 0.5)
+    }
+}
+
+/// Why a tile has no artwork yet.
+#[derive(Clone, Debug)]
+pub struct Failure
+{
+    pub reason: String,
+    pub attempts: u32,
+    pub retry_at: Option<Instant>, // `None` once the tile has been given up on
+}
+
+/// Downloads tile artwork on up to `workers` concurrent tasks and decodes it on
+/// the blocking pool. Finished images come back over a channel, ready for
+/// `glTexImage2D`, so the render loop can upload them at its own pace.
+///
+/// Tiles must be requested again every frame they are wanted: `dispatch` starts
+/// the most urgent requests and forgets the rest, so a tile that scrolled out of
+/// view before a worker was free is never downloaded.
+///
+/// Failed tiles are not requested again until their backoff has elapsed, and
+/// never again after a permanent error or too many attempts.
+pub struct ImageLoader
+{
+    source: Arc<ContentSource>,
+    client: HttpClient,
+    cache: Option<Arc<ArtworkCache>>,
+    workers: usize,
+    queued: HashMap<model::ImageDetails, (Priority, usize)>, // Requested this frame, ties go to the earliest
+    in_flight: HashSet<model::ImageDetails>,                 // Being downloaded or decoded
+    failures: HashMap<model::ImageDetails, Failure>,
+    retry: RetryPolicy,
+    results_sender: mpsc::UnboundedSender<ImageResult>,
+    results_receiver: mpsc::UnboundedReceiver<ImageResult>,
+    warnings_sender: mpsc::UnboundedSender<ContentError>,
+    warnings_receiver: mpsc::UnboundedReceiver<ContentError>,
+}
+
+impl ImageLoader
+{
+    pub fn new(
+        source: ContentSource,
+        client: HttpClient,
+        cache: Option<ArtworkCache>,
+        workers: usize,
+        retry: RetryPolicy,
+    ) -> Self
+    {
+        let (results_sender, results_receiver) = mpsc::unbounded_channel();
+        let (warnings_sender, warnings_receiver) = mpsc::unbounded_channel();
+
+        Self {
+            source: Arc::new(source),
+            client,
+            cache: cache.map(Arc::new),
+            workers: workers.max(1),
+            queued: HashMap::new(),
+            in_flight: HashSet::new(),
+            failures: HashMap::new(),
+            retry,
+            results_sender,
+            results_receiver,
+            warnings_sender,
+            warnings_receiver,
+        }
+    }
+
+    /// Queues a tile for the next `dispatch`, keeping its most urgent priority.
+    pub fn request(&mut self, tile: &model::ImageDetails, priority: Priority)
+    {
+        if self.in_flight.contains(tile)
+        {
+            return;
+        }
+
+        if let Some(failure) = self.failures.get(tile)
+        {
+            if failure.retry_at.is_none_or(|retry_at| Instant::now() < retry_at)
+            {
+                return;
+            }
+        }
+
+        let order = self.queued.len();
+        let queued = self.queued.entry(tile.clone()).or_insert((priority, order));
+        queued.0 = queued.0.min(priority);
+    }
+
+    /// Starts the most urgent queued downloads while there are idle workers and
+    /// drops every request that didn't get a worker.
+    pub fn dispatch(&mut self)
+    {
+        let idle_workers = self.workers.saturating_sub(self.in_flight.len());
+        let mut queued: Vec<_> = self.queued.drain().collect();
+        queued.sort_unstable_by_key(|(_, priority)| *priority);
+
+        for (tile, _) in queued.into_iter().take(idle_workers)
+        {
+            self.in_flight.insert(tile.clone());
+
+            let source = self.source.clone();
+            let client = self.client.clone();
+            let cache = self.cache.clone();
+            let results = self.results_sender.clone();
+            let warnings = self.warnings_sender.clone();
+
+            tokio::spawn(async move {
+                let result = load_image_from_http(&source, &client, cache, &warnings, &tile).await;
+
+                // The receiver lives as long as the loader, which only goes away on shutdown
+                let _ = results.send((tile, result));
+            });
+        }
+    }
+
+    /// The next finished image, if any, without blocking. Failures are
+    /// remembered for `failure` and scheduled for a retry if they may be
+    /// transient.
+    pub fn next_finished(&mut self) -> Option<ImageResult>
+    {
+        let (tile, result) = self.results_receiver.try_recv().ok()?;
+        self.in_flight.remove(&tile);
+
+        match result
+        {
+            Ok(_) =>
+            {
+                self.failures.remove(&tile);
+            }
+
+            Err(ref error) =>
+            {
+                let attempts = self.failures.get(&tile).map_or(0, |failure| failure.attempts) + 1;
+                let retry_at = (error.is_transient() && attempts < self.retry.max_attempts)
+                    .then(|| Instant::now() + self.retry.delay(attempts, jitter()));
+
+                self.failures.insert(tile.clone(), Failure { reason: error.to_string(), attempts, retry_at });
+            }
+        }
+
+        Some((tile, result))
+    }
+
+    /// The next problem that didn't stop a tile from showing, such as artwork
+    /// that couldn't be written to the disk cache.
+    pub fn next_warning(&mut self) -> Option<ContentError>
+    {
+        self.warnings_receiver.try_recv().ok()
+    }
+
+    /// Why the last attempt at a tile failed, if it did.
+    pub fn failure(&self, tile: &model::ImageDetails) -> Option<&Failure>
+    {
+        self.failures.get(tile)
+    }
+}
+
+/// A random number in `0.0 ..= 1.0`, good enough to spread retries out.
+fn jitter() -> f32
+{
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Checks the disk cache before going to the source, and stores whatever was
+/// downloaded once it is known to decode. Fixture directories are not cached.
+/// Failing to cache doesn't fail the load, it is sent to `warnings` instead.
+pub async fn load_image_from_http(
+    source: &ContentSource,
+    client: &HttpClient,
+    cache: Option<Arc<ArtworkCache>>,
+    warnings: &mpsc::UnboundedSender<ContentError>,
+    tile: &model::ImageDetails,
+) -> Result<image::RgbaImage, ContentError>
+{
+    let cache = cache.filter(|_| !matches!(source, ContentSource::Directory { .. }));
+
+    if let Some(cache) = cache.clone()
+    {
+        let tile = tile.clone();
+
+        let cached = tokio::task::spawn_blocking(move || {
+            let decoded = decode_image(&cache.get(&tile)?);
+
+            if decoded.is_err()
+            {
+                cache.remove(&tile);
+            }
+
+            decoded.ok()
+        });
+
+        if let Some(image) = cached.await.expect("Image decoder panicked")
+        {
+            return Ok(image);
+        }
+    }
+
+    let bytes = source.image(client, tile).await?;
+    let tile = tile.clone();
+    let warnings = warnings.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let image = decode_image(&bytes)?;
+
+        if let Some(cache) = cache
+        {
+            if let Err(error) = cache.put(&tile, &bytes)
+            {
+                let _ = warnings.send(ContentError::Io(cache.dir().to_owned(), error));
+            }
+        }
+
+        Ok(image)
+    })
+    .await
+    .expect("Image decoder panicked")
+}
+
+/// Decodes and converts to RGBA, both too slow to do on the render thread.
+pub fn decode_image(bytes: &[u8]) -> Result<image::RgbaImage, ContentError>
+{
+    // Guessing the format only reads from an in-memory cursor, which can't fail
+    let reader = image::io::Reader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()
+        .expect("Reading from memory cannot fail");
+
+    Ok(reader.decode()?.into_rgba8())
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::test_support::{self, tile};
+
+    #[tokio::test]
+    async fn loads_every_requested_tile()
+    {
+        let root = std::env::temp_dir().join(format!("portcullis-images-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("images")).unwrap();
+
+        for master_id in ["a", "b", "c"]
+        {
+            image::RgbImage::new(4, 4).save(root.join("images").join(format!("{}.jpg", master_id))).unwrap();
+        }
+
+        let mut images = ImageLoader::new(
+            ContentSource::Directory { root: root.clone() },
+            HttpClient::default(),
+            None,
+            2,
+            RetryPolicy::default(),
+        );
+        let mut results: Vec<(String, bool)> = Vec::new();
+
+        while results.len() < 4
+        {
+            for master_id in ["a", "b", "c", "missing", "a"]
+            {
+                if !results.iter().any(|(done, _)| done == master_id)
+                {
+                    images.request(&tile("http://localhost", master_id), Priority::Visible);
+                }
+            }
+
+            images.dispatch();
+            assert!(images.in_flight.len() <= 2);
+
+            while let Some((tile, result)) = images.next_finished()
+            {
+                results.push((tile.master_id, result.is_ok()));
+            }
+
+            tokio::task::yield_now().await;
+        }
+
+        results.sort();
+        assert_eq!(results, [("a".into(), true), ("b".into(), true), ("c".into(), true), ("missing".into(), false)]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn most_urgent_request_wins_and_the_rest_are_dropped()
+    {
+        let source = ContentSource::Directory { root: std::env::temp_dir() };
+        let mut images = ImageLoader::new(source, HttpClient::default(), None, 1, RetryPolicy::default());
+
+        images.request(&tile("http://localhost", "ahead"), Priority::Ahead);
+        images.request(&tile("http://localhost", "visible"), Priority::Visible);
+        images.request(&tile("http://localhost", "focused"), Priority::Ahead);
+        images.request(&tile("http://localhost", "focused"), Priority::Focused);
+        images.dispatch();
+
+        assert_eq!(images.in_flight.iter().map(|tile| tile.master_id.as_str()).collect::<Vec<_>>(), ["focused"]);
+        assert!(images.queued.is_empty());
+
+        // Requests for tiles already being downloaded are ignored
+        images.request(&tile("http://localhost", "focused"), Priority::Focused);
+        assert!(images.queued.is_empty());
+    }
+
+    async fn finish(images: &mut ImageLoader) -> ImageResult
+    {
+        loop
+        {
+            if let Some(finished) = images.next_finished()
+            {
+                return finished;
+            }
+
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried_until_the_last_attempt()
+    {
+        let server = test_support::MockServer::start().await;
+        let source = ContentSource::parse(&server.base_url);
+        let retry = RetryPolicy { max_attempts: 3, base_delay: Duration::ZERO, max_delay: Duration::ZERO };
+        let mut images = ImageLoader::new(source, HttpClient::default(), None, 1, retry);
+        let failing = server.tile("failing");
+        server.fail("/images/failing.jpg", test_support::Fault::ServerError);
+
+        for attempt in 1 ..= 3
+        {
+            images.request(&failing, Priority::Focused);
+            images.dispatch();
+            assert_eq!(images.in_flight.len(), 1);
+
+            assert!(finish(&mut images).await.1.is_err());
+            assert_eq!(images.failure(&failing).unwrap().attempts, attempt);
+        }
+
+        assert!(images.failure(&failing).unwrap().retry_at.is_none());
+        images.request(&failing, Priority::Focused);
+        assert!(images.queued.is_empty());
+        assert_eq!(server.hits("/images/failing.jpg"), 3);
+    }
+
+    #[tokio::test]
+    async fn permanent_failures_are_not_retried()
+    {
+        let source = ContentSource::Directory { root: std::env::temp_dir() };
+        let mut images = ImageLoader::new(source, HttpClient::default(), None, 1, RetryPolicy::default());
+
+        images.request(&tile("http://localhost", "missing"), Priority::Focused);
+        images.dispatch();
+
+        assert!(finish(&mut images).await.1.is_err());
+        let failure = images.failure(&tile("http://localhost", "missing")).unwrap();
+        assert!(failure.retry_at.is_none() && failure.reason.contains("missing"));
+
+        images.request(&tile("http://localhost", "missing"), Priority::Focused);
+        assert!(images.queued.is_empty());
+    }
+
+    #[tokio::test]
+    async fn downloads_from_the_mock_server()
+    {
+        let server = test_support::MockServer::start().await;
+        let source = ContentSource::parse(&server.base_url);
+        let client = HttpClient::default();
+        let (warnings, _) = mpsc::unbounded_channel();
+
+        let image = load_image_from_http(&source, &client, None, &warnings, &server.tile("a")).await.unwrap();
+        assert_eq!(image.dimensions(), (64, 36));
+
+        server.fail("/images/truncated.jpg", test_support::Fault::Truncated);
+        let error =
+            load_image_from_http(&source, &client, None, &warnings, &server.tile("truncated")).await.unwrap_err();
+        assert!(error.is_transient());
+
+        server.fail("/images/flaky.jpg", test_support::Fault::ServerError);
+        let error = load_image_from_http(&source, &client, None, &warnings, &server.tile("flaky")).await.unwrap_err();
+        assert!(error.is_transient());
+
+        server.fail("/images/missing.jpg", test_support::Fault::NotFound);
+        let error = load_image_from_http(&source, &client, None, &warnings, &server.tile("missing")).await.unwrap_err();
+        assert!(!error.is_transient());
+
+        server.serve("/images/garbage.jpg", "not a jpeg");
+        let error = load_image_from_http(&source, &client, None, &warnings, &server.tile("garbage")).await.unwrap_err();
+        assert!(matches!(error, ContentError::Image(_)));
+    }
+
+    #[tokio::test]
+    async fn cached_artwork_is_not_downloaded_again()
+    {
+        let server = test_support::MockServer::start().await;
+        let source = ContentSource::parse(&server.base_url);
+        let client = HttpClient::default();
+        let dir = std::env::temp_dir().join(format!("portcullis-images-cache-{}", std::process::id()));
+        let cache = Arc::new(ArtworkCache::open(dir.clone(), 1 << 20).unwrap());
+        let (warnings, mut warned) = mpsc::unbounded_channel();
+
+        for _ in 0 .. 2
+        {
+            let image = load_image_from_http(&source, &client, Some(cache.clone()), &warnings, &server.tile("a")).await;
+            assert_eq!(image.unwrap().dimensions(), (64, 36));
+        }
+
+        assert_eq!(server.hits("/images/a.jpg"), 1);
+        assert!(warned.try_recv().is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit()
+    {
+        let retry =
+            RetryPolicy { max_attempts: 10, base_delay: Duration::from_secs(1), max_delay: Duration::from_secs(5) };
+
+        assert_eq!(retry.delay(1, 1.0), Duration::from_secs(1));
+        assert_eq!(retry.delay(2, 1.0), Duration::from_secs(2));
+        assert_eq!(retry.delay(3, 0.0), Duration::from_secs(2));
+        assert_eq!(retry.delay(8, 1.0), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,5 @@
+//! Tile artwork: downloading and decoding, the disk cache and GPU textures.
+
+pub mod cache;
+pub mod images;
+pub mod textures;
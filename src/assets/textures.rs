@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use glow::{Context, HasContext, NativeTexture};
+
+/// Tile textures resident on the GPU, keyed by artwork URL.
+///
+/// Each texture is charged its approximate VRAM footprint (four bytes per
+/// pixel). Once the total goes past `budget_bytes` the textures drawn least
+/// recently are deleted. Evicted tiles simply look unloaded to the renderer,
+/// which requests them again if they come back into view. Textures drawn in the
+/// current or previous frame are never evicted, so a screen that needs more
+/// than the budget goes over it rather than thrashing.
+pub struct TextureManager<T = NativeTexture>
+{
+    textures: HashMap<String, Resident<T>>,
+    budget_bytes: usize,
+    used_bytes: usize,
+    frame: u64,
+}
+
+struct Resident<T>
+{
+    texture: T,
+    bytes: usize,
+    last_drawn: u64, // Frame number
+}
+
+impl<T: Copy> TextureManager<T>
+{
+    pub fn new(budget_bytes: usize) -> Self
+    {
+        Self { textures: HashMap::new(), budget_bytes, used_bytes: 0, frame: 0 }
+    }
+
+    /// Call once per frame before any texture is drawn.
+    pub fn begin_frame(&mut self)
+    {
+        self.frame += 1;
+    }
+
+    pub fn contains(&self, url: &str) -> bool
+    {
+        self.textures.contains_key(url)
+    }
+
+    /// The texture for `url`, marked as drawn this frame.
+    pub fn get(&mut self, url: &str) -> Option<T>
+    {
+        let resident = self.textures.get_mut(url)?;
+        resident.last_drawn = self.frame;
+        Some(resident.texture)
+    }
+
+    /// Tracks a new texture and returns the ones that have to be deleted to get
+    /// back under budget, including any texture previously stored for `url`.
+    fn insert(&mut self, url: String, texture: T, bytes: usize) -> Vec<T>
+    {
+        let mut evicted = Vec::new();
+        let resident = Resident { texture, bytes, last_drawn: self.frame };
+
+        if let Some(replaced) = self.textures.insert(url, resident)
+        {
+            self.used_bytes -= replaced.bytes;
+            evicted.push(replaced.texture);
+        }
+
+        self.used_bytes += bytes;
+
+        while self.used_bytes > self.budget_bytes
+        {
+            let oldest = self
+                .textures
+                .iter()
+                .filter(|(_, resident)| resident.last_drawn + 1 < self.frame)
+                .min_by_key(|(_, resident)| resident.last_drawn)
+                .map(|(url, _)| url.clone());
+
+            match oldest.and_then(|url| self.textures.remove(&url))
+            {
+                Some(resident) =>
+                {
+                    self.used_bytes -= resident.bytes;
+                    evicted.push(resident.texture);
+                }
+
+                None => break, // Everything left is on screen
+            }
+        }
+
+        evicted
+    }
+
+    fn drain(&mut self) -> Vec<T>
+    {
+        self.used_bytes = 0;
+        self.textures.drain().map(|(_, resident)| resident.texture).collect()
+    }
+}
+
+impl TextureManager<NativeTexture>
+{
+    /// Uploads a decoded tile and deletes whatever it pushed out of the budget.
+    pub unsafe fn upload(&mut self, gl: &Context, url: String, image: &image::RgbaImage)
+    {
+        let bytes = image.width() as usize * image.height() as usize * 4;
+
+        for texture in self.insert(url, upload_image_to_gpu(gl, image), bytes)
+        {
+            gl.delete_texture(texture);
+        }
+    }
+
+    pub unsafe fn delete_all(&mut self, gl: &Context)
+    {
+        for texture in self.drain()
+        {
+            gl.delete_texture(texture);
+        }
+    }
+}
+
+/// Only the copy to the GPU happens here, decoding and conversion to RGBA are
+/// done beforehand so that a tile arriving doesn't hitch the frame.
+pub unsafe fn upload_image_to_gpu(gl: &Context, image: &image::RgbaImage) -> NativeTexture
+{
+    let texture = gl.create_texture().unwrap();
+
+    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR as i32);
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
+
+    gl.tex_image_2d(
+        glow::TEXTURE_2D,
+        0,
+        glow::RGBA8 as i32,
+        image.width() as i32,
+        image.height() as i32,
+        0,
+        glow::RGBA,
+        glow::UNSIGNED_BYTE,
+        Some(image.as_raw()),
+    );
+
+    gl.bind_texture(glow::TEXTURE_2D, None);
+
+    texture
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_drawn()
+    {
+        let mut textures = TextureManager::<u32>::new(300);
+
+        textures.begin_frame();
+        assert!(textures.insert("a".into(), 1, 100).is_empty());
+        assert!(textures.insert("b".into(), 2, 100).is_empty());
+        assert!(textures.insert("c".into(), 3, 100).is_empty());
+
+        // Two frames later only "a" and "c" are still drawn, "b" goes first
+        textures.begin_frame();
+        textures.begin_frame();
+        textures.get("a");
+        textures.get("c");
+        textures.begin_frame();
+        textures.begin_frame();
+        textures.get("c");
+
+        assert_eq!(textures.insert("d".into(), 4, 100), [2]);
+        assert_eq!(textures.insert("e".into(), 5, 100), [1]);
+        assert!(!textures.contains("a") && !textures.contains("b"));
+        assert_eq!(textures.used_bytes, 300);
+
+        // Replacing a texture frees the old one and its bytes
+        assert_eq!(textures.insert("e".into(), 6, 50), [5]);
+        assert_eq!(textures.used_bytes, 250);
+    }
+
+    #[test]
+    fn never_evicts_what_is_on_screen()
+    {
+        let mut textures = TextureManager::<u32>::new(100);
+
+        textures.begin_frame();
+        assert!(textures.insert("a".into(), 1, 100).is_empty());
+        assert!(textures.insert("b".into(), 2, 100).is_empty());
+        assert_eq!(textures.used_bytes, 200);
+
+        textures.begin_frame();
+        textures.get("b");
+        textures.begin_frame();
+
+        // "a" wasn't drawn last frame and can go, "b" was and has to stay
+        assert_eq!(textures.insert("c".into(), 3, 100), [1]);
+        assert_eq!(textures.drain().len(), 2);
+        assert_eq!(textures.used_bytes, 0);
+    }
+}
@@ -0,0 +1,331 @@
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::assets::cache::ArtworkCache;
+use crate::assets::images::RetryPolicy;
+use crate::content::cache::DocumentCache;
+use crate::content::error::ContentError;
+use crate::content::source::{ContentSource, HttpClient};
+use crate::ui::input::Bindings;
+use crate::ui::layout::{Breakpoint, LayoutSettings};
+use crate::ui::navigation::Navigation;
+
+/// Runtime settings. Each one can be given on the command line as
+/// `--name value` (or `--name=value`) or through a `PORTCULLIS_NAME` environment
+/// variable, in that order of precedence.
+#[derive(Clone, Debug)]
+pub struct Config
+{
+    pub source: ContentSource,
+
+    /// How long to wait for a TCP/TLS connection to the content server.
+    pub connect_timeout: Duration,
+
+    /// How long a response may go quiet, before its headers or between two
+    /// parts of its body, before it fails. Downloads that keep making progress
+    /// can take as long as they need.
+    pub read_timeout: Duration,
+
+    /// Every request goes through this proxy when set. Otherwise the usual
+    /// `HTTP_PROXY`/`HTTPS_PROXY`/`NO_PROXY` environment variables apply.
+    pub proxy: Option<String>,
+
+    /// How often home.json and the referenced sets are checked for changes,
+    /// `None` never checks.
+    pub refresh_interval: Option<Duration>,
+
+    /// How many `sets/{refId}.json` requests may be in flight at once.
+    pub set_concurrency: usize,
+
+    /// How many tile images may be downloaded at once.
+    pub image_workers: usize,
+
+    /// Backoff for artwork that failed to download. Only the attempt count is
+    /// configurable.
+    pub image_retry: RetryPolicy,
+
+    /// How many finished images are uploaded to the GPU per frame, at least one.
+    pub uploads_per_frame: usize,
+
+    /// No more uploads are started once this much of a frame has been spent on
+    /// them.
+    pub upload_budget: Duration,
+
+    /// Tile textures drawn least recently are deleted from the GPU past this
+    /// size.
+    pub texture_budget_mb: usize,
+
+    /// Where downloaded artwork, and the last content for offline launches, is
+    /// kept between launches. `None` disables the cache.
+    pub cache_dir: Option<PathBuf>,
+
+    /// Least recently used artwork is evicted past this size.
+    pub cache_size_mb: u64,
+
+    /// Key bindings to apply on top of the defaults, see `Bindings`.
+    pub input_config: Option<PathBuf>,
+
+    /// What moving past the ends of a row or of the rows does.
+    pub navigation: Navigation,
+
+    /// Tiles per row and safe area. The scale comes from `ui_scale`.
+    pub layout: LayoutSettings,
+
+    /// Overrides the scale worked out from the display DPI.
+    pub ui_scale: Option<f32>,
+}
+
+impl Default for Config
+{
+    fn default() -> Self
+    {
+        Self {
+            source: ContentSource::default(),
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(10),
+            proxy: None,
+            refresh_interval: None,
+            set_concurrency: 4,
+            image_workers: 8,
+            image_retry: RetryPolicy::default(),
+            uploads_per_frame: 4,
+            upload_budget: Duration::from_millis(4),
+            texture_budget_mb: 128,
+            cache_dir: ArtworkCache::default_dir(),
+            cache_size_mb: 256,
+            input_config: dirs::config_dir().map(|dir| dir.join("portcullis").join("input.json")),
+            navigation: Navigation::default(),
+            layout: LayoutSettings::default(),
+            ui_scale: None,
+        }
+    }
+}
+
+impl Config
+{
+    /// Opens the artwork cache, `None` if it is disabled.
+    pub fn open_cache(&self) -> Result<Option<ArtworkCache>, ContentError>
+    {
+        let Some(cache_dir) = self.cache_dir.clone()
+        else
+        {
+            return Ok(None);
+        };
+
+        ArtworkCache::open(cache_dir.clone(), self.cache_size_mb * 1024 * 1024)
+            .map(Some)
+            .map_err(|error| ContentError::Io(cache_dir, error))
+    }
+
+    /// Opens the cache of `home.json` and set bodies, kept in the `documents`
+    /// directory of the artwork cache. `None` if the cache is disabled.
+    pub fn open_documents(&self) -> Result<Option<DocumentCache>, ContentError>
+    {
+        let Some(dir) = self.cache_dir.as_ref().map(|cache_dir| cache_dir.join("documents"))
+        else
+        {
+            return Ok(None);
+        };
+
+        DocumentCache::open(dir.clone()).map(Some).map_err(|error| ContentError::Io(dir, error))
+    }
+
+    /// The key bindings, the defaults if there is no bindings file. Debug
+    /// bindings are only kept in debug builds.
+    pub fn load_bindings(&self) -> Result<Bindings, String>
+    {
+        let debug = cfg!(debug_assertions);
+
+        let path = match self.input_config
+        {
+            Some(ref path) if path.exists() => path,
+            _ => return Ok(Bindings::defaults(debug)),
+        };
+
+        std::fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|json| Bindings::from_json(&json, debug).map_err(|error| error.to_string()))
+            .map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    /// The client shared by every request: keep-alive, gzip and timeouts so a
+    /// server that stops answering fails the request instead of stalling it.
+    pub fn http_client(&self) -> HttpClient
+    {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(concat!("Portcullis/", env!("CARGO_PKG_VERSION")))
+            .connect_timeout(self.connect_timeout)
+            .pool_idle_timeout(Duration::from_secs(90))
+            .tcp_keepalive(Duration::from_secs(60))
+            .gzip(true);
+
+        // `from_env` only keeps a proxy that parses
+        if let Some(proxy) = self.proxy.as_deref().and_then(|proxy| reqwest::Proxy::all(proxy).ok())
+        {
+            builder = builder.proxy(proxy);
+        }
+
+        HttpClient::new(builder.build().expect("Failed to initialize the HTTP client"), self.read_timeout)
+    }
+
+    /// The settings from the command line and environment, along with a
+    /// message for every value that was ignored because it was invalid.
+    pub fn from_env() -> (Self, Vec<String>)
+    {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let mut config = Config::default();
+        let mut warnings = Vec::new();
+
+        if let Some(source) = option(&args, "source")
+        {
+            config.source = ContentSource::parse(&source);
+        }
+
+        if let Some(connect_timeout_ms) = parsed_option(&args, "connect-timeout-ms", &mut warnings)
+        {
+            config.connect_timeout = Duration::from_millis(connect_timeout_ms);
+        }
+
+        if let Some(read_timeout_ms) = parsed_option(&args, "read-timeout-ms", &mut warnings)
+        {
+            config.read_timeout = Duration::from_millis(read_timeout_ms);
+        }
+
+        if let Some(proxy) = option(&args, "proxy")
+        {
+            match reqwest::Proxy::all(&proxy)
+            {
+                Ok(_) => config.proxy = Some(proxy),
+                Err(error) => warnings.push(format!("Ignoring invalid proxy {:?}: {}", proxy, error)),
+            }
+        }
+
+        if let Some(refresh_secs) = parsed_option(&args, "refresh-secs", &mut warnings)
+        {
+            config.refresh_interval = (refresh_secs > 0).then(|| Duration::from_secs(refresh_secs));
+        }
+
+        if let Some(set_concurrency) = parsed_option(&args, "set-concurrency", &mut warnings)
+        {
+            config.set_concurrency = set_concurrency;
+        }
+
+        if let Some(image_workers) = parsed_option(&args, "image-workers", &mut warnings)
+        {
+            config.image_workers = image_workers;
+        }
+
+        if let Some(image_attempts) = parsed_option(&args, "image-attempts", &mut warnings)
+        {
+            config.image_retry.max_attempts = image_attempts;
+        }
+
+        // None at all would leave every decoded image waiting forever
+        if let Some(uploads_per_frame) = parsed_option::<NonZeroUsize>(&args, "uploads-per-frame", &mut warnings)
+        {
+            config.uploads_per_frame = uploads_per_frame.get();
+        }
+
+        if let Some(upload_budget_ms) = parsed_option(&args, "upload-budget-ms", &mut warnings)
+        {
+            config.upload_budget = Duration::from_millis(upload_budget_ms);
+        }
+
+        if let Some(texture_budget_mb) = parsed_option(&args, "texture-budget-mb", &mut warnings)
+        {
+            config.texture_budget_mb = texture_budget_mb;
+        }
+
+        if let Some(cache_dir) = option(&args, "cache-dir")
+        {
+            config.cache_dir = Some(PathBuf::from(cache_dir));
+        }
+
+        if let Some(cache_size_mb) = parsed_option(&args, "cache-size-mb", &mut warnings)
+        {
+            config.cache_size_mb = cache_size_mb;
+        }
+
+        if let Some(input_config) = option(&args, "input-config")
+        {
+            config.input_config = Some(PathBuf::from(input_config));
+        }
+
+        if let Some(column_edges) = parsed_option(&args, "column-edges", &mut warnings)
+        {
+            config.navigation.columns = column_edges;
+        }
+
+        if let Some(row_edges) = parsed_option(&args, "row-edges", &mut warnings)
+        {
+            config.navigation.rows = row_edges;
+        }
+
+        if let Some(tiles_per_row) = option(&args, "tiles-per-row")
+        {
+            match tiles_per_row.split(',').map(str::parse).collect::<Result<Vec<Breakpoint>, _>>()
+            {
+                Ok(breakpoints) => config.layout.breakpoints = breakpoints,
+                Err(error) => warnings.push(format!("Ignoring invalid value for --tiles-per-row: {}", error)),
+            }
+        }
+
+        if let Some(safe_area) = parsed_option(&args, "safe-area", &mut warnings)
+        {
+            config.layout.safe_area = safe_area;
+        }
+
+        if let Some(ui_scale) = parsed_option::<f32>(&args, "ui-scale", &mut warnings).filter(|scale| *scale > 0.0)
+        {
+            config.ui_scale = Some(ui_scale);
+        }
+
+        if config.cache_size_mb == 0
+        {
+            config.cache_dir = None;
+        }
+
+        (config, warnings)
+    }
+}
+
+fn option(args: &[String], name: &str) -> Option<String>
+{
+    let flag = format!("--{}", name);
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next()
+    {
+        if *arg == flag
+        {
+            if let Some(value) = args.next()
+            {
+                return Some(value.clone());
+            }
+        }
+        else if let Some(value) = arg.strip_prefix(&flag).and_then(|rest| rest.strip_prefix('='))
+        {
+            return Some(value.to_owned());
+        }
+    }
+
+    let variable = format!("PORTCULLIS_{}", name.to_uppercase().replace('-', "_"));
+
+    std::env::var(variable).ok().filter(|value| !value.is_empty())
+}
+
+fn parsed_option<T: std::str::FromStr>(args: &[String], name: &str, warnings: &mut Vec<String>) -> Option<T>
+{
+    let value = option(args, name)?;
+
+    match value.parse()
+    {
+        Ok(value) => Some(value),
+        Err(_) =>
+        {
+            warnings.push(format!("Ignoring invalid value for --{}: {:?}", name, value));
+            None
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::assets::cache::{decode_entry, encode_entry, fnv1a, write_entry};
+
+/// The last good body of `home.json` and of every set, keyed by URL, so the app
+/// can start without a network. Entries are checked like artwork entries and
+/// dropped when corrupted.
+#[derive(Debug)]
+pub struct DocumentCache
+{
+    dir: PathBuf,
+}
+
+/// A saved body and the `ETag` it was served with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedDocument
+{
+    pub etag: Option<String>,
+    pub body: String,
+}
+
+impl DocumentCache
+{
+    pub fn open(dir: PathBuf) -> io::Result<Self>
+    {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    pub fn get(&self, url: &str) -> Option<SavedDocument>
+    {
+        let path = self.path(url);
+        let entry = fs::read(&path).ok()?;
+
+        // The ETag goes on the first line, it can't contain a line break
+        let document = decode_entry(&entry).and_then(|payload| {
+            let (etag, body) = std::str::from_utf8(payload).ok()?.split_once('\n')?;
+            Some(SavedDocument { etag: (!etag.is_empty()).then(|| etag.to_owned()), body: body.to_owned() })
+        });
+
+        if document.is_none()
+        {
+            let _ = fs::remove_file(&path);
+        }
+
+        document
+    }
+
+    pub fn put(&self, url: &str, document: &SavedDocument) -> io::Result<()>
+    {
+        let payload = format!("{}\n{}", document.etag.as_deref().unwrap_or_default(), document.body);
+        write_entry(&self.path(url), &encode_entry(payload.as_bytes()))
+    }
+
+    /// Named by a hash of the whole URL, so URLs that differ only in
+    /// punctuation don't share an entry.
+    fn path(&self, url: &str) -> PathBuf
+    {
+        self.dir.join(format!("{:016x}.doc", fnv1a(url.as_bytes())))
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn round_trip_and_corruption()
+    {
+        let dir = std::env::temp_dir().join(format!("portcullis-documents-{}", std::process::id()));
+        let cache = DocumentCache::open(dir.clone()).unwrap();
+        let url = "http://localhost/home.json";
+        let document = SavedDocument { etag: Some(String::from("\"abc\"")), body: String::from("{\n}") };
+
+        assert!(cache.get(url).is_none());
+        cache.put(url, &document).unwrap();
+        assert_eq!(cache.get(url), Some(document.clone()));
+
+        let untagged = SavedDocument { etag: None, body: String::from("{}") };
+        cache.put(url, &untagged).unwrap();
+        assert_eq!(cache.get(url), Some(untagged.clone()));
+
+        // URLs that only differ in punctuation are kept apart
+        let other = "http://localhost/home_json";
+        assert!(cache.get(other).is_none());
+        cache.put(other, &document).unwrap();
+        assert_eq!(cache.get(url), Some(untagged));
+
+        // A truncated entry is rejected and removed
+        let path = cache.path(url);
+        let entry = fs::read(&path).unwrap();
+        fs::write(&path, &entry[.. entry.len() - 1]).unwrap();
+        assert!(cache.get(url).is_none());
+        assert!(!path.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinSet;
+
+use crate::content::error::ContentError;
+use crate::content::model;
+use crate::content::source::{ContentSource, HttpClient, Revalidation};
+
+#[derive(Clone)]
+pub struct Collection
+{
+    pub container_index: usize, // Position in home.json, rows are kept in this order
+    pub set_id: String,         // Identifies the row across refreshes
+    pub name: String,
+    pub videos: Vec<Video>,
+    pub selected_video: i32,
+    pub scroll: f32, // Columns scrolled past the first tile, kept while other rows are focused
+    pub state: RowState,
+    pub skipped: Vec<String>, // Items that couldn't be displayed, and why
+}
+
+#[derive(Clone, PartialEq)]
+pub enum RowState
+{
+    Loading,        // Drawn as a skeleton until the referenced set arrives
+    Ready,          // Videos are available
+    Failed(String), // Shown in place of the tiles
+}
+
+/// Sent from `get_collections` as content becomes available.
+pub enum ContentUpdate
+{
+    /// Every row in home.json order, with referenced sets still loading.
+    Home(Vec<Collection>),
+
+    /// A referenced set finished loading (or failed) and replaces its placeholder.
+    Row(Collection),
+}
+
+pub const CONTENT_NOT_SET: usize = 50000;
+
+#[derive(Clone)]
+pub struct Video
+{
+    pub name: String,
+    pub content_id: String,
+    pub tile: model::ImageDetails,         // The variant drawn, at the width it is drawn
+    pub variants: Vec<model::TileVariant>, // Every shape of tile the item has, to pick from as the layout changes
+    pub previous_tile: Option<model::ImageDetails>, // Drawn until `tile` has been downloaded
+    pub content_index: usize,
+}
+
+impl Video
+{
+    /// Switches to the variant closest to `aspect_ratio`, requested `width`
+    /// pixels wide. The tile on screen stays there until the new one arrives,
+    /// however many times the tile changes in between.
+    pub fn pick_tile(&mut self, aspect_ratio: f32, width: u32)
+    {
+        let Some(variant) = model::closest(&self.variants, aspect_ratio)
+        else
+        {
+            return;
+        };
+
+        let tile = variant.image.with_width(width);
+
+        if tile == self.tile
+        {
+            return;
+        }
+
+        let replaced = std::mem::replace(&mut self.tile, tile);
+
+        // A tile replaced while another was still on screen was never downloaded
+        match self.previous_tile
+        {
+            None => self.previous_tile = Some(replaced),
+            Some(ref previous) if *previous == self.tile => self.previous_tile = None,
+            Some(_) => (),
+        }
+    }
+
+    /// `url` has been uploaded, so the tile waiting for it no longer needs the
+    /// one it replaced.
+    pub fn tile_arrived(&mut self, url: &str)
+    {
+        if self.tile.url == url
+        {
+            self.previous_tile = None;
+        }
+    }
+}
+
+fn handle_item(item: &model::Item, aspect_ratio: f32) -> Result<Video, ContentError>
+{
+    let unknown = || ContentError::UnknownItemType(item.item_type().to_owned());
+    let content_type = item.content_type().ok_or_else(unknown)?;
+    let content_id = item.content_id().unwrap_or_default();
+
+    let name = item.title().ok_or_else(|| {
+        ContentError::Schema(format!("{} {} has no {} title", item.item_type(), content_id, content_type))
+    })?;
+
+    let variants = item.image().ok_or_else(unknown)?.tile.variants(content_type);
+    let tile = model::closest(&variants, aspect_ratio)
+        .ok_or_else(|| ContentError::Schema(format!("{} {} has no tile image", item.item_type(), content_id)))?
+        .image
+        .clone();
+
+    Ok(Video {
+        name: name.to_owned(),
+        content_id: content_id.to_owned(),
+        tile,
+        variants,
+        previous_tile: None,
+        content_index: CONTENT_NOT_SET,
+    })
+}
+
+/// Turns a loaded (or failed) set into a row. Items that can't be displayed are
+/// skipped and listed with the reason, so that one bad item doesn't cost the
+/// whole row.
+fn build_collection(
+    container_index: usize,
+    set_id: String,
+    name: String,
+    set: Result<model::CuratedSet, ContentError>,
+    aspect_ratio: f32,
+) -> Collection
+{
+    let mut collection = Collection {
+        container_index,
+        set_id,
+        name,
+        videos: Vec::new(),
+        selected_video: 0,
+        scroll: 0.0,
+        state: RowState::Ready,
+        skipped: Vec::new(),
+    };
+
+    match set
+    {
+        Ok(set) =>
+        {
+            for item in &set.items
+            {
+                match handle_item(item, aspect_ratio)
+                {
+                    Ok(video) => collection.videos.push(video),
+                    Err(error) => collection.skipped.push(error.to_string()),
+                }
+            }
+
+            if collection.videos.is_empty()
+            {
+                collection.state = RowState::Failed(String::from("Nothing to show"));
+            }
+        }
+
+        Err(error) => collection.state = RowState::Failed(error.to_string()),
+    }
+
+    collection
+}
+
+/// Loads home.json and sends every row at once, with rows included in home.json
+/// ready to show and referenced sets as placeholders. The referenced sets are
+/// then resolved concurrently, with at most `concurrency` requests in flight,
+/// and each is sent as soon as it is ready. Returns once every row has been
+/// sent, with what `refresh_collections` needs to only fetch what changed.
+pub async fn get_collections(
+    source: ContentSource,
+    client: HttpClient,
+    aspect_ratio: f32,
+    concurrency: usize,
+    updates: mpsc::UnboundedSender<ContentUpdate>,
+) -> Result<Revalidation, ContentError>
+{
+    let mut revalidation = Revalidation::default();
+    let (home, _) = source.revalidate_home(&client, &mut revalidation).await?;
+    let containers = home.data.standard_collection.containers;
+    let mut collections = Vec::with_capacity(containers.len());
+    let mut set_refs = Vec::new();
+
+    for (container_index, container) in containers.into_iter().enumerate()
+    {
+        let name = container.set.title().unwrap_or_default().to_owned();
+        let set_id = container.set.id().to_owned();
+
+        match container.set
+        {
+            model::Set::CuratedSet(set) =>
+            {
+                collections.push(build_collection(container_index, set_id, name, Ok(set), aspect_ratio));
+            }
+
+            model::Set::SetRef(set_ref) =>
+            {
+                collections.push(Collection {
+                    container_index,
+                    set_id,
+                    name: name.clone(),
+                    videos: Vec::new(),
+                    selected_video: 0,
+                    scroll: 0.0,
+                    state: RowState::Loading,
+                    skipped: Vec::new(),
+                });
+
+                set_refs.push((container_index, name, set_ref));
+            }
+        }
+    }
+
+    // The receiver only goes away when the app is shutting down or retrying
+    let _ = updates.send(ContentUpdate::Home(collections));
+
+    let source = Arc::new(source);
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut set_requests = JoinSet::new();
+
+    for (container_index, name, set_ref) in set_refs
+    {
+        let (source, client, permits, updates) = (source.clone(), client.clone(), permits.clone(), updates.clone());
+
+        set_requests.spawn(async move {
+            let mut revalidation = Revalidation::default();
+            let set = {
+                let _permit = permits.acquire().await.expect("Semaphore is never closed");
+                source.revalidate_set(&client, &set_ref.ref_id, &mut revalidation).await.map(|(set, _)| set)
+            };
+
+            let set_id = set_ref.ref_id.clone();
+            let collection = build_collection(container_index, set_id, name, set, aspect_ratio);
+            let _ = updates.send(ContentUpdate::Row(collection));
+
+            revalidation
+        });
+    }
+
+    while let Some(set_revalidation) = set_requests.join_next().await
+    {
+        revalidation.extend(set_revalidation.expect("Set request panicked"));
+    }
+
+    Ok(revalidation)
+}
+
+/// Re-fetches home.json and every referenced set once per `interval` with
+/// conditional requests, starting from the `revalidation` `get_collections`
+/// returned, and sends every row again whenever any of them
+/// changed, or the error if they couldn't be fetched. Runs until the receiver
+/// goes away.
+pub async fn refresh_collections(
+    source: ContentSource,
+    client: HttpClient,
+    aspect_ratio: f32,
+    interval: std::time::Duration,
+    mut revalidation: Revalidation,
+    updates: mpsc::UnboundedSender<Result<Vec<Collection>, ContentError>>,
+)
+{
+    loop
+    {
+        tokio::time::sleep(interval).await;
+
+        let update = match revalidate_collections(&source, &client, aspect_ratio, &mut revalidation).await
+        {
+            Ok(Some(collections)) => Ok(collections),
+            Ok(None) => continue,
+            Err(error) => Err(error),
+        };
+
+        if updates.send(update).is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// Every row, or `None` if neither home.json nor any referenced set changed.
+/// Sets are fetched one at a time, there is no hurry in the background.
+async fn revalidate_collections(
+    source: &ContentSource,
+    client: &HttpClient,
+    aspect_ratio: f32,
+    revalidation: &mut Revalidation,
+) -> Result<Option<Vec<Collection>>, ContentError>
+{
+    let (home, mut changed) = source.revalidate_home(client, revalidation).await?;
+    let mut sets = Vec::new();
+
+    for container in home.data.standard_collection.containers
+    {
+        let name = container.set.title().unwrap_or_default().to_owned();
+        let set_id = container.set.id().to_owned();
+
+        let set = match container.set
+        {
+            model::Set::CuratedSet(set) => Ok(set),
+            model::Set::SetRef(set_ref) =>
+            {
+                source.revalidate_set(client, &set_ref.ref_id, revalidation).await.map(|(set, set_changed)| {
+                    changed |= set_changed;
+                    set
+                })
+            }
+        };
+
+        sets.push((set_id, name, set));
+    }
+
+    if !changed
+    {
+        return Ok(None);
+    }
+
+    let collections = sets
+        .into_iter()
+        .enumerate()
+        .map(|(container_index, (set_id, name, set))| {
+            build_collection(container_index, set_id, name, set, aspect_ratio)
+        })
+        .collect();
+
+    Ok(Some(collections))
+}
+
+/// Swaps in refreshed rows while keeping what the user is looking at: the
+/// focused row is found again by set id and each row's focused tile by
+/// `contentId`. A row that failed to refresh keeps its previous content.
+pub fn merge_collections(live: &mut Vec<Collection>, refreshed: Vec<Collection>, selection: &mut glam::Vec2)
+{
+    let focused_set = live.get(selection.y as usize).map(|row| row.set_id.clone());
+    let mut previous: HashMap<String, Collection> = live.drain(..).map(|row| (row.set_id.clone(), row)).collect();
+
+    for mut row in refreshed
+    {
+        if let Some(old) = previous.remove(&row.set_id)
+        {
+            if matches!(row.state, RowState::Failed(_)) && old.state == RowState::Ready
+            {
+                row = Collection { container_index: row.container_index, name: row.name, ..old };
+            }
+            else
+            {
+                let focused = old.videos.get(old.selected_video as usize).map(|video| &video.content_id);
+                let last = row.videos.len().saturating_sub(1);
+
+                row.selected_video = focused
+                    .and_then(|content_id| row.videos.iter().position(|video| video.content_id == *content_id))
+                    .unwrap_or((old.selected_video as usize).min(last)) as i32;
+
+                // The focused tile stays where it was on screen
+                row.scroll = (old.scroll + (row.selected_video - old.selected_video) as f32).clamp(0.0, last as f32);
+
+                // Tiles keep playing the same content
+                for video in &mut row.videos
+                {
+                    if let Some(old_video) =
+                        old.videos.iter().find(|old_video| old_video.content_id == video.content_id)
+                    {
+                        video.content_index = old_video.content_index;
+                    }
+                }
+            }
+        }
+
+        live.push(row);
+    }
+
+    let last_row = live.len().saturating_sub(1);
+
+    selection.y = focused_set
+        .and_then(|set_id| live.iter().position(|row| row.set_id == set_id))
+        .unwrap_or((selection.y as usize).min(last_row)) as f32;
+    selection.x = live.get(selection.y as usize).map_or(0.0, |row| row.selected_video as f32);
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::config::Config;
+    use crate::content::cache::DocumentCache;
+    use crate::test_support::row;
+
+    #[tokio::test]
+    async fn referenced_sets_arrive_after_placeholders()
+    {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let source = ContentSource::parse("src/home.json");
+
+        get_collections(source, HttpClient::default(), 0.5, 2, sender).await.unwrap();
+
+        let home = match receiver.recv().await
+        {
+            Some(ContentUpdate::Home(home)) => home,
+            _ => panic!("home must be sent first"),
+        };
+
+        assert_eq!(home.len(), 13);
+        assert!(home[.. 4].iter().all(|row| row.state == RowState::Ready && !row.videos.is_empty()));
+        assert!(home[4 ..].iter().all(|row| row.state == RowState::Loading));
+
+        // A single file can't resolve referenced sets, so every placeholder is replaced by a failed row
+        let mut replaced = Vec::new();
+        while let Some(ContentUpdate::Row(row)) = receiver.recv().await
+        {
+            assert!(matches!(row.state, RowState::Failed(_)));
+            replaced.push(row.container_index);
+        }
+
+        replaced.sort_unstable();
+        assert_eq!(replaced, (4 .. 13).collect::<Vec<_>>());
+    }
+
+    async fn load_all(server: &crate::test_support::MockServer, client: HttpClient) -> Vec<Collection>
+    {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        get_collections(ContentSource::parse(&server.base_url), client, 0.5, 4, sender).await.unwrap();
+
+        let mut rows = match receiver.recv().await
+        {
+            Some(ContentUpdate::Home(home)) => home,
+            _ => panic!("home must be sent first"),
+        };
+
+        while let Some(ContentUpdate::Row(row)) = receiver.recv().await
+        {
+            let index = row.container_index;
+            rows[index] = row;
+        }
+
+        rows
+    }
+
+    #[tokio::test]
+    async fn collections_load_from_the_mock_server()
+    {
+        let server = crate::test_support::MockServer::start().await;
+        server.set_latency(std::time::Duration::from_millis(20));
+        server.fail("/sets/bd1bfb9a-bbf7-43a0-ac5e-3e3889d7224d.json", crate::test_support::Fault::ServerError);
+        server.fail("/sets/25b87551-fd19-421a-be0f-b7f2eea978b3.json", crate::test_support::Fault::MalformedJson);
+
+        let rows = load_all(&server, HttpClient::default()).await;
+
+        assert_eq!(rows.len(), 13);
+        assert!(matches!(rows[4].state, RowState::Failed(ref error) if error.contains("500")));
+        assert!(matches!(rows[5].state, RowState::Failed(ref error) if error.contains("JSON")));
+        assert!(rows[6 ..].iter().all(|row| row.state == RowState::Ready && row.videos.len() == 6));
+        assert!(rows[6].videos[0].tile.url.starts_with(&server.base_url));
+    }
+
+    #[tokio::test]
+    async fn first_refresh_only_revalidates()
+    {
+        let server = crate::test_support::MockServer::start().await;
+        let source = ContentSource::parse(&server.base_url);
+        let client = HttpClient::default();
+        let (sender, _receiver) = mpsc::unbounded_channel();
+
+        let mut revalidation = get_collections(source.clone(), client.clone(), 0.5, 4, sender).await.unwrap();
+
+        // Nothing changed since the launch, every request is answered with 304
+        let refreshed = revalidate_collections(&source, &client, 0.5, &mut revalidation).await.unwrap();
+        assert!(refreshed.is_none());
+        assert_eq!(server.not_modified(), 10);
+
+        let home = std::fs::read_to_string("src/home.json").unwrap();
+        server.serve("/home.json", home.replacen("New to Disney+", "Just Added", 1));
+        let refreshed = revalidate_collections(&source, &client, 0.5, &mut revalidation).await.unwrap();
+        assert_eq!(refreshed.unwrap()[0].name, "Just Added");
+    }
+
+    #[tokio::test]
+    async fn saved_content_is_shown_offline()
+    {
+        let server = crate::test_support::MockServer::start().await;
+        let dir = std::env::temp_dir().join(format!("portcullis-offline-{}", std::process::id()));
+        let client = || HttpClient::default().with_documents(DocumentCache::open(dir.clone()).unwrap());
+
+        load_all(&server, client()).await;
+
+        // The next launch only revalidates what it saved
+        let rows = load_all(&server, client()).await;
+        assert!(rows.iter().all(|row| row.state == RowState::Ready));
+        assert_eq!(server.not_modified(), 10);
+
+        // Unreachable, it starts with the saved rows
+        server.fail("/home.json", crate::test_support::Fault::ServerError);
+        server.fail("/sets/bd1bfb9a-bbf7-43a0-ac5e-3e3889d7224d.json", crate::test_support::Fault::Truncated);
+        let offline = load_all(&server, client()).await;
+        assert!(offline.iter().all(|row| row.state == RowState::Ready));
+        assert_eq!(offline[4].videos.len(), rows[4].videos.len());
+
+        // Content that is gone for good isn't brought back
+        server.fail("/home.json", crate::test_support::Fault::NotFound);
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        let source = ContentSource::parse(&server.base_url);
+        assert!(get_collections(source, client(), 0.5, 4, sender).await.is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn undisplayable_items_are_listed()
+    {
+        let set: model::CuratedSet =
+            model::from_str(r#"{"setId": "x", "text": {}, "items": [{"type": "DmcPodcast", "text": {}}]}"#).unwrap();
+
+        let row = build_collection(0, String::from("x"), String::from("Podcasts"), Ok(set), 0.5);
+
+        assert!(matches!(row.state, RowState::Failed(_)));
+        assert_eq!(row.skipped, vec![String::from("unknown item type \"DmcPodcast\"")]);
+    }
+
+    #[tokio::test]
+    async fn broken_or_hanging_home_is_an_error()
+    {
+        let server = crate::test_support::MockServer::start().await;
+        let source = ContentSource::parse(&server.base_url);
+        let (sender, _receiver) = mpsc::unbounded_channel();
+
+        server.fail("/home.json", crate::test_support::Fault::ServerError);
+        let error = get_collections(source.clone(), HttpClient::default(), 0.5, 4, sender.clone()).await.unwrap_err();
+        assert!(matches!(error, ContentError::HttpStatus { .. }));
+
+        server.fail("/home.json", crate::test_support::Fault::Truncated);
+        let error = get_collections(source.clone(), HttpClient::default(), 0.5, 4, sender.clone()).await.unwrap_err();
+        assert!(matches!(error, ContentError::Network(_)));
+
+        // A server that never answers in time fails the request instead of stalling it
+        let config = Config { read_timeout: std::time::Duration::from_millis(100), ..Config::default() };
+        server.fail("/home.json", crate::test_support::Fault::MalformedJson);
+        server.set_latency(std::time::Duration::from_secs(5));
+        let error = get_collections(source.clone(), config.http_client(), 0.5, 4, sender.clone()).await.unwrap_err();
+        assert!(matches!(error, ContentError::Timeout { .. }));
+        assert!(error.is_transient());
+
+        // One that answers slowly but steadily takes as long as it needs
+        let config = Config { read_timeout: crate::test_support::TRICKLE_PAUSE * 3, ..Config::default() };
+        server.set_latency(std::time::Duration::ZERO);
+        server.fail("/home.json", crate::test_support::Fault::Trickle);
+        let started = std::time::Instant::now();
+        get_collections(source, config.http_client(), 0.5, 4, sender).await.unwrap();
+        assert!(started.elapsed() > config.read_timeout);
+    }
+
+    #[test]
+    fn refresh_keeps_the_focused_row_and_tile()
+    {
+        let mut live =
+            vec![row("a", ["1", "2"]), Collection { selected_video: 1, scroll: 1.0, ..row("b", ["3", "4", "5"]) }];
+        let mut selection = glam::vec2(1.0, 1.0);
+
+        // A new row is inserted above and the focused tile moved within its row
+        let mut failed = row("a", 0 .. 0);
+        failed.state = RowState::Failed(String::from("HTTP 503"));
+        let refreshed = vec![row("new", ["6"]), failed, row("b", ["7", "4", "3"])];
+
+        merge_collections(&mut live, refreshed, &mut selection);
+
+        assert_eq!(live.iter().map(|row| row.set_id.as_str()).collect::<Vec<_>>(), ["new", "a", "b"]);
+        assert_eq!(selection, glam::vec2(1.0, 2.0));
+        assert_eq!(live[2].selected_video, 1);
+
+        // The row that failed to refresh keeps what it had
+        assert!(live[1].state == RowState::Ready);
+        assert_eq!(live[1].videos.len(), 2);
+
+        // When the focused row disappears the selection stays in range
+        merge_collections(&mut live, vec![row("a", ["1"])], &mut selection);
+        assert_eq!(selection, glam::vec2(0.0, 0.0));
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::content::model::ParseError;
+
+/// Anything that can go wrong while loading collections or artwork.
+#[derive(Debug)]
+pub enum ContentError
+{
+    /// The request never produced a response (DNS, connection...).
+    Network(reqwest::Error),
+
+    /// The server went quiet for longer than the read timeout, before
+    /// answering or partway through the body.
+    Timeout
+    {
+        url: String
+    },
+
+    /// A local content file could not be read.
+    Io(PathBuf, std::io::Error),
+
+    /// The server answered with a non-success status code.
+    HttpStatus
+    {
+        url: String, status: reqwest::StatusCode
+    },
+
+    /// The body was not valid JSON or did not match the model.
+    Json(ParseError),
+
+    /// The JSON parsed but is missing something the app needs.
+    Schema(String),
+
+    /// An item whose `type` the app does not know how to display.
+    UnknownItemType(String),
+
+    /// The image bytes could not be decoded.
+    Image(image::ImageError),
+}
+
+impl fmt::Display for ContentError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            ContentError::Network(error) => write!(f, "network error: {}", error),
+            ContentError::Timeout { url } => write!(f, "timed out waiting for {}", url),
+            ContentError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            ContentError::HttpStatus { url, status } => write!(f, "HTTP {} for {}", status, url),
+            ContentError::Json(error) => write!(f, "unexpected JSON at {}: {}", error.path(), error.inner()),
+            ContentError::Schema(message) => write!(f, "unexpected schema: {}", message),
+            ContentError::UnknownItemType(item_type) => write!(f, "unknown item type {:?}", item_type),
+            ContentError::Image(error) => write!(f, "failed to decode image: {}", error),
+        }
+    }
+}
+
+impl ContentError
+{
+    /// Whether trying again later might succeed: the network failed or the
+    /// server had a problem. Missing content and undecodable data won't fix
+    /// themselves.
+    pub fn is_transient(&self) -> bool
+    {
+        match self
+        {
+            ContentError::Network(_) | ContentError::Timeout { .. } => true,
+            ContentError::HttpStatus { status, .. } =>
+            {
+                status.is_server_error()
+                    || *status == reqwest::StatusCode::REQUEST_TIMEOUT
+                    || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::error::Error for ContentError
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
+    {
+        match self
+        {
+            ContentError::Network(error) => Some(error),
+            ContentError::Io(_, error) => Some(error),
+            ContentError::Json(error) => Some(error),
+            ContentError::Image(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ContentError
+{
+    fn from(error: reqwest::Error) -> Self
+    {
+        ContentError::Network(error)
+    }
+}
+
+impl From<ParseError> for ContentError
+{
+    fn from(error: ParseError) -> Self
+    {
+        ContentError::Json(error)
+    }
+}
+
+impl From<image::ImageError> for ContentError
+{
+    fn from(error: image::ImageError) -> Self
+    {
+        ContentError::Image(error)
+    }
+}
@@ -0,0 +1,8 @@
+//! The home screen API: its JSON model, where it is loaded from and how it is
+//! turned into rows.
+
+pub mod cache;
+pub mod collections;
+pub mod error;
+pub mod model;
+pub mod source;
@@ -0,0 +1,572 @@
+//! Typed view of the `home.json` and `sets/{refId}.json` documents.
+//!
+//! Unknown fields are ignored so the API can grow without breaking the app, but
+//! any field the app relies on must be present and well-typed.
+
+use std::collections::HashMap;
+
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer};
+
+pub type ParseError = serde_path_to_error::Error<serde_json::Error>;
+
+/// Deserialize a document, reporting the JSON path that failed to match.
+pub fn from_str<T: DeserializeOwned>(json: &str) -> Result<T, ParseError>
+{
+    let deserializer = &mut serde_json::Deserializer::from_str(json);
+    serde_path_to_error::deserialize(deserializer)
+}
+
+/// Root of `home.json`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Home
+{
+    pub data: HomeData,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct HomeData
+{
+    #[serde(rename = "StandardCollection")]
+    pub standard_collection: StandardCollection,
+}
+
+/// Root of `sets/{refId}.json`. The single key under `data` names the set type
+/// (`CuratedSet`, `TrendingSet`, `PersonalizedCuratedSet`, ...).
+#[derive(Clone, Debug, Deserialize)]
+pub struct SetResponse
+{
+    pub data: HashMap<String, CuratedSet>,
+}
+
+impl SetResponse
+{
+    pub fn into_set(self) -> Option<CuratedSet>
+    {
+        self.data.into_values().next()
+    }
+}
+
+/// Used both as the root collection of `home.json` and as an item linking to
+/// another collection (e.g. "Disney Channel").
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StandardCollection
+{
+    pub collection_id: String,
+    pub text: Text,
+    #[serde(default)]
+    pub image: Images,
+    #[serde(default)]
+    pub video_art: Vec<VideoArt>,
+    #[serde(default)]
+    pub containers: Vec<Container>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Container
+{
+    pub set: Set,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub style: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum Set
+{
+    CuratedSet(CuratedSet),
+    SetRef(SetRef),
+}
+
+impl Set
+{
+    /// `setId` of an inline set, `refId` of a referenced one.
+    pub fn id(&self) -> &str
+    {
+        match self
+        {
+            Set::CuratedSet(set) => &set.set_id,
+            Set::SetRef(set) => &set.ref_id,
+        }
+    }
+
+    pub fn title(&self) -> Option<&str>
+    {
+        match self
+        {
+            Set::CuratedSet(set) => set.text.full_title("set"),
+            Set::SetRef(set) => set.text.full_title("set"),
+        }
+    }
+}
+
+/// A set whose items are included inline.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CuratedSet
+{
+    pub set_id: String,
+    pub content_class: Option<String>,
+    pub text: Text,
+    pub items: Vec<Item>,
+}
+
+/// A set whose items must be fetched from `sets/{refId}.json`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetRef
+{
+    pub ref_id: String,
+    pub ref_id_type: Option<String>,
+    pub ref_type: Option<String>,
+    pub content_class: Option<String>,
+    pub text: Text,
+}
+
+#[derive(Clone, Debug)]
+pub enum Item
+{
+    DmcSeries(DmcSeries),
+    DmcVideo(DmcVideo),
+    StandardCollection(StandardCollection),
+
+    /// An item `type` this app doesn't know about. Kept so a new type only
+    /// costs a tile rather than the whole set.
+    Unknown(String),
+}
+
+impl<'de> Deserialize<'de> for Item
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let item_type = value
+            .get("type")
+            .and_then(serde_json::Value::as_str)
+            .ok_or_else(|| de::Error::missing_field("type"))?
+            .to_owned();
+
+        // Keep the path inside the item in the message, as the outer path stops here
+        fn variant<T: DeserializeOwned, E: de::Error>(value: serde_json::Value) -> Result<T, E>
+        {
+            serde_path_to_error::deserialize(value).map_err(|error| E::custom(format!("{}: {}", error.path(), error)))
+        }
+
+        match item_type.as_str()
+        {
+            "DmcSeries" => variant(value).map(Item::DmcSeries),
+            "DmcVideo" => variant(value).map(Item::DmcVideo),
+            "StandardCollection" => variant(value).map(Item::StandardCollection),
+            _ => Ok(Item::Unknown(item_type)),
+        }
+    }
+}
+
+impl Item
+{
+    /// The `type` field as it appeared in the JSON.
+    pub fn item_type(&self) -> &str
+    {
+        match self
+        {
+            Item::DmcSeries(_) => "DmcSeries",
+            Item::DmcVideo(_) => "DmcVideo",
+            Item::StandardCollection(_) => "StandardCollection",
+            Item::Unknown(item_type) => item_type,
+        }
+    }
+
+    /// The key used for this item type in the `text` and `image` maps.
+    pub fn content_type(&self) -> Option<&'static str>
+    {
+        match self
+        {
+            Item::DmcSeries(_) => Some("series"),
+            Item::DmcVideo(_) => Some("program"),
+            Item::StandardCollection(_) => Some("collection"),
+            Item::Unknown(_) => None,
+        }
+    }
+
+    pub fn content_id(&self) -> Option<&str>
+    {
+        match self
+        {
+            Item::DmcSeries(series) => Some(&series.content_id),
+            Item::DmcVideo(video) => Some(&video.content_id),
+            Item::StandardCollection(collection) => Some(&collection.collection_id),
+            Item::Unknown(_) => None,
+        }
+    }
+
+    pub fn text(&self) -> Option<&Text>
+    {
+        match self
+        {
+            Item::DmcSeries(series) => Some(&series.text),
+            Item::DmcVideo(video) => Some(&video.text),
+            Item::StandardCollection(collection) => Some(&collection.text),
+            Item::Unknown(_) => None,
+        }
+    }
+
+    pub fn image(&self) -> Option<&Images>
+    {
+        match self
+        {
+            Item::DmcSeries(series) => Some(&series.image),
+            Item::DmcVideo(video) => Some(&video.image),
+            Item::StandardCollection(collection) => Some(&collection.image),
+            Item::Unknown(_) => None,
+        }
+    }
+
+    pub fn title(&self) -> Option<&str>
+    {
+        self.text()?.full_title(self.content_type()?)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DmcSeries
+{
+    pub content_id: String,
+    pub series_id: String,
+    pub text: Text,
+    pub image: Images,
+    #[serde(default)]
+    pub ratings: Vec<Rating>,
+    #[serde(default)]
+    pub releases: Vec<Release>,
+    #[serde(default)]
+    pub video_art: Vec<VideoArt>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DmcVideo
+{
+    pub content_id: String,
+    pub program_id: Option<String>,
+    pub program_type: Option<String>,
+    pub text: Text,
+    pub image: Images,
+    #[serde(default)]
+    pub ratings: Vec<Rating>,
+    #[serde(default)]
+    pub releases: Vec<Release>,
+    #[serde(default)]
+    pub video_art: Vec<VideoArt>,
+}
+
+/// `text.title.{full,slug}.{contentType}.default`
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Text
+{
+    #[serde(default)]
+    pub title: TitleText,
+}
+
+impl Text
+{
+    pub fn full_title(&self, content_type: &str) -> Option<&str>
+    {
+        self.title.full.get(content_type).map(|entry| entry.default.content.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TitleText
+{
+    #[serde(default)]
+    pub full: HashMap<String, TextEntry>,
+    #[serde(default)]
+    pub slug: HashMap<String, TextEntry>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TextEntry
+{
+    pub default: TextContent,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextContent
+{
+    pub content: String,
+    pub language: Option<String>,
+    pub source_entity: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Images
+{
+    #[serde(default)]
+    pub tile: TileMap,
+}
+
+/// `image.tile.{aspectRatio}.{contentType | "default"}.default`
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct TileMap(pub HashMap<String, HashMap<String, ImageEntry>>);
+
+impl TileMap
+{
+    /// Every aspect ratio's tile, preferring the artwork for `content_type` and
+    /// falling back to the `default` entry. Sorted from tallest to widest. A key
+    /// that isn't a number takes the shape of the master instead.
+    pub fn variants(&self, content_type: &str) -> Vec<TileVariant>
+    {
+        let mut variants: Vec<_> = self
+            .0
+            .iter()
+            .filter_map(|(ratio, variants)| {
+                let entry = variants.get(content_type).or_else(|| variants.get("default"))?;
+                let aspect_ratio = ratio.parse().ok().or_else(|| entry.default.aspect_ratio())?;
+                Some(TileVariant { aspect_ratio, image: entry.default.clone() })
+            })
+            .collect();
+
+        variants.sort_by(|a, b| a.aspect_ratio.total_cmp(&b.aspect_ratio));
+        variants
+    }
+}
+
+/// One tile image and the aspect ratio (width over height) it is cropped to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileVariant
+{
+    pub aspect_ratio: f32,
+    pub image: ImageDetails,
+}
+
+/// The variant whose aspect ratio is closest to `aspect_ratio`.
+pub fn closest(variants: &[TileVariant], aspect_ratio: f32) -> Option<&TileVariant>
+{
+    variants
+        .iter()
+        .min_by(|a, b| (a.aspect_ratio - aspect_ratio).abs().total_cmp(&(b.aspect_ratio - aspect_ratio).abs()))
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ImageEntry
+{
+    pub default: ImageDetails,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageDetails
+{
+    pub master_id: String,
+    pub master_width: u32,
+    pub master_height: u32,
+    pub url: String,
+}
+
+impl ImageDetails
+{
+    /// Width over height of the master, `None` when either is unknown.
+    pub fn aspect_ratio(&self) -> Option<f32>
+    {
+        (self.master_width > 0 && self.master_height > 0).then(|| self.master_width as f32 / self.master_height as f32)
+    }
+
+    /// The same image requested `width` pixels wide, at most as wide as the
+    /// master. A master missing either dimension is of unknown size and isn't
+    /// clamped. Only URLs that already ask for a width are changed.
+    pub fn with_width(&self, width: u32) -> Self
+    {
+        let width = match self.aspect_ratio()
+        {
+            Some(_) => width.min(self.master_width),
+            None => width,
+        };
+
+        let url = match self.url.split_once('?')
+        {
+            Some((path, query)) if query.split('&').any(|parameter| parameter.starts_with("width=")) =>
+            {
+                let query: Vec<_> = query
+                    .split('&')
+                    .map(|parameter| match parameter.starts_with("width=")
+                    {
+                        true => format!("width={}", width),
+                        false => parameter.to_owned(),
+                    })
+                    .collect();
+
+                format!("{}?{}", path, query.join("&"))
+            }
+
+            _ => self.url.clone(),
+        };
+
+        Self { url, ..self.clone() }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Rating
+{
+    #[serde(default)]
+    pub advisories: Vec<String>,
+    pub description: Option<String>,
+    pub system: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Release
+{
+    pub release_date: Option<String>,
+    pub release_type: String,
+    pub release_year: Option<u32>,
+    pub territory: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoArt
+{
+    pub media_metadata: MediaMetadata,
+    pub purpose: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MediaMetadata
+{
+    pub urls: Vec<MediaUrl>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MediaUrl
+{
+    pub url: String,
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn parse_bundled_home()
+    {
+        let home: Home = from_str(include_str!("../home.json")).unwrap();
+        let containers = &home.data.standard_collection.containers;
+
+        assert_eq!(containers.len(), 13);
+        assert_eq!(containers[0].set.title(), Some("New to Disney+"));
+
+        let curated = containers.iter().filter(|c| matches!(c.set, Set::CuratedSet(_))).count();
+        let refs = containers.iter().filter(|c| matches!(c.set, Set::SetRef(_))).count();
+        assert_eq!((curated, refs), (4, 9));
+
+        for container in containers
+        {
+            if let Set::CuratedSet(set) = &container.set
+            {
+                for item in &set.items
+                {
+                    let content_type = item.content_type().unwrap();
+                    assert!(item.title().is_some(), "{:?} has no title", item.content_id());
+                    assert!(!item.image().unwrap().tile.variants(content_type).is_empty());
+                }
+            }
+        }
+
+        // Ratings, releases and video art come through on the first tile
+        let Set::CuratedSet(set) = &containers[0].set
+        else
+        {
+            panic!("the first row is included in home.json");
+        };
+        let Item::DmcSeries(series) = &set.items[0]
+        else
+        {
+            panic!("the first tile is a series");
+        };
+
+        assert_eq!((series.ratings[0].system.as_str(), series.ratings[0].value.as_str()), ("TVPG", "TV-14"));
+        assert_eq!(series.releases[0].release_type, "original");
+        assert_eq!(series.releases[0].release_year, Some(2020));
+        assert_eq!(series.video_art[0].purpose, "full_bleed");
+        assert!(series.video_art[0].media_metadata.urls[0].url.ends_with(".mp4"));
+    }
+
+    #[test]
+    fn error_names_path()
+    {
+        let json = r#"{"data": {"StandardCollection": {"collectionId": "x", "text": {}, "containers": [
+            {"type": "ShelfContainer", "set": {"type": "SetRef", "text": {}}}
+        ]}}}"#;
+
+        let error = from_str::<Home>(json).unwrap_err();
+
+        assert!(error.path().to_string().starts_with("data.StandardCollection.containers[0]"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_item_type_is_kept()
+    {
+        let set: CuratedSet =
+            from_str(r#"{"setId": "x", "text": {}, "items": [{"type": "DmcPodcast", "text": {}}]}"#).unwrap();
+
+        assert!(matches!(&set.items[0], Item::Unknown(item_type) if item_type == "DmcPodcast"));
+    }
+
+    #[test]
+    fn tiles_fall_back_to_default()
+    {
+        let tiles: TileMap = serde_json::from_str(
+            r#"{
+                "1.78": {"default": {"default": {"masterId": "a", "masterWidth": 1, "masterHeight": 1, "url": "wide"}}},
+                "0.75": {"series": {"default": {"masterId": "b", "masterWidth": 1, "masterHeight": 1, "url": "tall"}}}
+            }"#,
+        )
+        .unwrap();
+
+        let series = tiles.variants("series");
+        assert_eq!(closest(&series, 2.0).unwrap().image.url, "wide");
+        assert_eq!(closest(&series, 0.7).unwrap().image.url, "tall");
+
+        // Only the default artwork is left for other content types, whatever its shape
+        let program = tiles.variants("program");
+        assert_eq!(program.len(), 1);
+        assert_eq!(closest(&program, 0.7).unwrap().image.url, "wide");
+        assert!(closest(&[], 0.7).is_none());
+
+        // Without a usable key the master's own shape is used
+        let tiles: TileMap = serde_json::from_str(
+            r#"{"poster": {"default": {"default": {"masterId": "c", "masterWidth": 3, "masterHeight": 4, "url": "c"}}}}"#,
+        )
+        .unwrap();
+        assert_eq!(tiles.variants("series")[0].aspect_ratio, 0.75);
+    }
+
+    #[test]
+    fn artwork_is_requested_at_the_drawn_width()
+    {
+        let image = ImageDetails {
+            master_id: String::from("a"),
+            master_width: 1920,
+            master_height: 1080,
+            url: String::from("https://example.com/a/scale?format=jpeg&width=500&quality=90"),
+        };
+
+        assert_eq!(image.with_width(300).url, "https://example.com/a/scale?format=jpeg&width=300&quality=90");
+        assert_eq!(image.with_width(4000).url, "https://example.com/a/scale?format=jpeg&width=1920&quality=90");
+
+        let unknown = ImageDetails { master_height: 0, ..image.clone() };
+        assert_eq!(unknown.with_width(4000).url, "https://example.com/a/scale?format=jpeg&width=4000&quality=90");
+
+        let fixed = ImageDetails { url: String::from("https://example.com/a.jpg?quality=90"), ..image };
+        assert_eq!(fixed.with_width(300).url, fixed.url);
+    }
+}
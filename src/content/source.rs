@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::header::{self, HeaderValue};
+
+use crate::content::cache::{DocumentCache, SavedDocument};
+use crate::content::error::ContentError;
+use crate::content::model;
+
+pub const DEFAULT_BASE_URL: &str = "https://cd-static.bamgrid.com/dp-117731241344";
+
+/// The client shared by every request, and how long a response may go quiet,
+/// waiting for its headers or for the next part of its body, before it fails.
+/// A slow download that keeps making progress is never cut off.
+#[derive(Clone, Debug)]
+pub struct HttpClient
+{
+    client: reqwest::Client,
+    read_timeout: Duration,
+    documents: Option<Arc<DocumentCache>>, // Where the JSON documents fetched are saved for offline launches
+}
+
+impl Default for HttpClient
+{
+    fn default() -> Self
+    {
+        Self::new(reqwest::Client::new(), Duration::from_secs(10))
+    }
+}
+
+impl HttpClient
+{
+    pub fn new(client: reqwest::Client, read_timeout: Duration) -> Self
+    {
+        Self { client, read_timeout, documents: None }
+    }
+
+    /// Saves every `home.json` and set fetched to `documents`, and answers from
+    /// there when the server can't be reached.
+    pub fn with_documents(self, documents: DocumentCache) -> Self
+    {
+        Self { documents: Some(Arc::new(documents)), ..self }
+    }
+
+    fn saved(&self, url: &str) -> Option<SavedDocument>
+    {
+        self.documents.as_ref()?.get(url)
+    }
+
+    fn save(&self, url: &str, etag: Option<&HeaderValue>, body: &str)
+    {
+        if let Some(ref documents) = self.documents
+        {
+            let etag = etag.and_then(|etag| etag.to_str().ok()).map(str::to_owned);
+
+            // Failing to save only costs the offline fallback, the fetch itself succeeded
+            let _ = documents.put(url, &SavedDocument { etag, body: body.to_owned() });
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder, url: &str) -> Result<reqwest::Response, ContentError>
+    {
+        let timeout = || ContentError::Timeout { url: url.to_owned() };
+        Ok(tokio::time::timeout(self.read_timeout, request.send()).await.map_err(|_| timeout())??)
+    }
+
+    async fn bytes(&self, mut response: reqwest::Response, url: &str) -> Result<Vec<u8>, ContentError>
+    {
+        let timeout = || ContentError::Timeout { url: url.to_owned() };
+        let mut body = Vec::new();
+
+        while let Some(chunk) =
+            tokio::time::timeout(self.read_timeout, response.chunk()).await.map_err(|_| timeout())??
+        {
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(body)
+    }
+
+    async fn text(&self, response: reqwest::Response, url: &str) -> Result<String, ContentError>
+    {
+        let body = self.bytes(response, url).await?;
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+}
+
+/// Where `home.json`, the referenced sets and the tile artwork come from.
+#[derive(Clone, Debug)]
+pub enum ContentSource
+{
+    /// `{base_url}/home.json` and `{base_url}/sets/{refId}.json`. Artwork is
+    /// fetched from the absolute URLs found in the JSON.
+    Http
+    {
+        base_url: String
+    },
+
+    /// A single `home.json`. Only `CuratedSet` rows can be shown, artwork is
+    /// still fetched over HTTP.
+    File
+    {
+        path: PathBuf
+    },
+
+    /// A fixture directory laid out as `home.json`, `sets/{refId}.json` and
+    /// `images/{masterId}.jpg`. Nothing touches the network.
+    Directory
+    {
+        root: PathBuf
+    },
+}
+
+impl Default for ContentSource
+{
+    fn default() -> Self
+    {
+        ContentSource::Http { base_url: DEFAULT_BASE_URL.to_owned() }
+    }
+}
+
+impl ContentSource
+{
+    /// Interprets a URL, a directory or a file path.
+    pub fn parse(source: &str) -> Self
+    {
+        if source.starts_with("http://") || source.starts_with("https://")
+        {
+            ContentSource::Http { base_url: source.trim_end_matches('/').to_owned() }
+        }
+        else if Path::new(source).is_dir()
+        {
+            ContentSource::Directory { root: PathBuf::from(source) }
+        }
+        else
+        {
+            ContentSource::File { path: PathBuf::from(source) }
+        }
+    }
+
+    pub async fn home(&self, client: &HttpClient) -> Result<model::Home, ContentError>
+    {
+        Ok(self.revalidate_home(client, &mut Revalidation::default()).await?.0)
+    }
+
+    pub async fn set(&self, client: &HttpClient, ref_id: &str) -> Result<model::CuratedSet, ContentError>
+    {
+        Ok(self.revalidate_set(client, ref_id, &mut Revalidation::default()).await?.0)
+    }
+
+    /// Like `home`, also telling whether it changed since the last call with
+    /// the same `revalidation`, never the first time. Over HTTP this is a
+    /// conditional request.
+    pub async fn revalidate_home(
+        &self,
+        client: &HttpClient,
+        revalidation: &mut Revalidation,
+    ) -> Result<(model::Home, bool), ContentError>
+    {
+        let (body, changed) = match self
+        {
+            ContentSource::Http { base_url } =>
+            {
+                fetch_text_revalidated(client, &format!("{base_url}/home.json"), revalidation).await?
+            }
+
+            ContentSource::File { path } => revalidation.compare(path.display().to_string(), read_text(path).await?),
+
+            ContentSource::Directory { root } =>
+            {
+                let path = root.join("home.json");
+                revalidation.compare(path.display().to_string(), read_text(&path).await?)
+            }
+        };
+
+        Ok((model::from_str(&body)?, changed))
+    }
+
+    /// Like `set`, also telling whether it changed since the last call with the
+    /// same `revalidation`, never the first time.
+    pub async fn revalidate_set(
+        &self,
+        client: &HttpClient,
+        ref_id: &str,
+        revalidation: &mut Revalidation,
+    ) -> Result<(model::CuratedSet, bool), ContentError>
+    {
+        let (body, changed) = match self
+        {
+            ContentSource::Http { base_url } =>
+            {
+                fetch_text_revalidated(client, &format!("{base_url}/sets/{ref_id}.json"), revalidation).await?
+            }
+
+            ContentSource::Directory { root } =>
+            {
+                let path = root.join("sets").join(format!("{ref_id}.json"));
+                revalidation.compare(path.display().to_string(), read_text(&path).await?)
+            }
+
+            ContentSource::File { path } =>
+            {
+                return Err(ContentError::Schema(format!("set {} is not included in {}", ref_id, path.display())))
+            }
+        };
+
+        let set = model::from_str::<model::SetResponse>(&body)?
+            .into_set()
+            .ok_or_else(|| ContentError::Schema(format!("set {} has no data", ref_id)))?;
+
+        Ok((set, changed))
+    }
+
+    /// The encoded bytes of a tile's artwork.
+    pub async fn image(&self, client: &HttpClient, tile: &model::ImageDetails) -> Result<Vec<u8>, ContentError>
+    {
+        match self
+        {
+            ContentSource::Directory { root } =>
+            {
+                let path = root.join("images").join(format!("{}.jpg", tile.master_id));
+                tokio::fs::read(&path).await.map_err(|error| ContentError::Io(path, error))
+            }
+
+            _ => client.bytes(fetch(client, &tile.url).await?, &tile.url).await,
+        }
+    }
+}
+
+async fn fetch(client: &HttpClient, url: &str) -> Result<reqwest::Response, ContentError>
+{
+    let response = client.send(client.client.get(url), url).await?;
+
+    if !response.status().is_success()
+    {
+        return Err(ContentError::HttpStatus { url: url.to_owned(), status: response.status() });
+    }
+
+    Ok(response)
+}
+
+/// The last body and validators of every document fetched through
+/// `revalidate_home` and `revalidate_set`, keyed by URL or path.
+#[derive(Debug, Default)]
+pub struct Revalidation
+{
+    documents: HashMap<String, Document>,
+}
+
+#[derive(Debug)]
+struct Document
+{
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    body: String,
+}
+
+impl Revalidation
+{
+    /// Takes in the documents `other` has seen, such as sets fetched on their
+    /// own tasks.
+    pub fn extend(&mut self, other: Revalidation)
+    {
+        self.documents.extend(other.documents);
+    }
+
+    /// Stores a freshly read body, returning it along with whether it differs
+    /// from the previous one. Without a previous one there is nothing to
+    /// compare to, so it hasn't changed.
+    fn compare(&mut self, key: String, body: String) -> (String, bool)
+    {
+        self.store(key, None, None, body)
+    }
+
+    fn store(
+        &mut self,
+        key: String,
+        etag: Option<HeaderValue>,
+        last_modified: Option<HeaderValue>,
+        body: String,
+    ) -> (String, bool)
+    {
+        let changed = self.documents.get(&key).is_some_and(|document| document.body != body);
+        self.documents.insert(key, Document { etag, last_modified, body: body.clone() });
+
+        (body, changed)
+    }
+}
+
+/// Sends the stored validators along, and answers from the stored body when the
+/// server replies `304 Not Modified`. A document this session hasn't loaded yet
+/// starts from the copy saved on disk, which also stands in for it while the
+/// server can't be reached.
+async fn fetch_text_revalidated(
+    client: &HttpClient,
+    url: &str,
+    revalidation: &mut Revalidation,
+) -> Result<(String, bool), ContentError>
+{
+    let first_load = !revalidation.documents.contains_key(url);
+
+    if let Some(saved) = client.saved(url).filter(|_| first_load)
+    {
+        let etag = saved.etag.and_then(|etag| HeaderValue::from_str(&etag).ok());
+        revalidation.documents.insert(url.to_owned(), Document { etag, last_modified: None, body: saved.body });
+    }
+
+    let mut request = client.client.get(url);
+    let stored = revalidation.documents.get(url);
+
+    if let Some(document) = stored
+    {
+        if let Some(ref etag) = document.etag
+        {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+
+        if let Some(ref last_modified) = document.last_modified
+        {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let stored = stored.map(|document| document.body.clone());
+
+    let fetched = async {
+        let response = client.send(request, url).await?;
+
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED && stored.is_some()
+        {
+            return Ok(None);
+        }
+
+        if !response.status().is_success()
+        {
+            return Err(ContentError::HttpStatus { url: url.to_owned(), status: response.status() });
+        }
+
+        let etag = response.headers().get(header::ETAG).cloned();
+        let last_modified = response.headers().get(header::LAST_MODIFIED).cloned();
+        let body = client.text(response, url).await?;
+
+        Ok(Some((etag, last_modified, body)))
+    };
+
+    match (fetched.await, stored)
+    {
+        (Ok(Some((etag, last_modified, body))), _) =>
+        {
+            client.save(url, etag.as_ref(), &body);
+            Ok(revalidation.store(url.to_owned(), etag, last_modified, body))
+        }
+
+        (Ok(None), Some(body)) => Ok((body, false)),
+        (Err(error), Some(body)) if first_load && error.is_transient() => Ok((body, false)),
+        (Err(error), _) => Err(error),
+        (Ok(None), None) => unreachable!("Only a stored document is revalidated"),
+    }
+}
+
+async fn read_text(path: &Path) -> Result<String, ContentError>
+{
+    tokio::fs::read_to_string(path).await.map_err(|error| ContentError::Io(path.to_owned(), error))
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::test_support::MockServer;
+
+    #[test]
+    fn parse_source()
+    {
+        assert!(matches!(
+            ContentSource::parse("http://localhost:8000/"),
+            ContentSource::Http { base_url } if base_url == "http://localhost:8000"
+        ));
+        assert!(matches!(ContentSource::parse("src"), ContentSource::Directory { .. }));
+        assert!(matches!(ContentSource::parse("src/home.json"), ContentSource::File { .. }));
+    }
+
+    #[tokio::test]
+    async fn single_file_has_no_sets()
+    {
+        let source = ContentSource::parse("src/home.json");
+        let client = HttpClient::default();
+
+        assert_eq!(source.home(&client).await.unwrap().data.standard_collection.containers.len(), 13);
+        assert!(matches!(
+            source.set(&client, "bd1bfb9a-bbf7-43a0-ac5e-3e3889d7224d").await,
+            Err(ContentError::Schema(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn revalidation_notices_changes()
+    {
+        let root = std::env::temp_dir().join(format!("portcullis-revalidation-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::copy("src/home.json", root.join("home.json")).unwrap();
+
+        let source = ContentSource::Directory { root: root.clone() };
+        let client = HttpClient::default();
+        let mut revalidation = Revalidation::default();
+
+        // There is nothing to compare the first read to
+        assert!(!source.revalidate_home(&client, &mut revalidation).await.unwrap().1);
+        assert!(!source.revalidate_home(&client, &mut revalidation).await.unwrap().1);
+
+        let home = std::fs::read_to_string("src/home.json").unwrap();
+        std::fs::write(root.join("home.json"), home.replacen("New to Disney+", "Just Added", 1)).unwrap();
+
+        let (home, changed) = source.revalidate_home(&client, &mut revalidation).await.unwrap();
+        assert!(changed);
+        assert_eq!(home.data.standard_collection.containers[0].set.title(), Some("Just Added"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn conditional_requests_use_the_etag()
+    {
+        let server = MockServer::start().await;
+        let source = ContentSource::parse(&server.base_url);
+        let client = HttpClient::default();
+        let mut revalidation = Revalidation::default();
+
+        assert!(!source.revalidate_set(&client, "a", &mut revalidation).await.unwrap().1);
+
+        let (set, changed) = source.revalidate_set(&client, "a", &mut revalidation).await.unwrap();
+        assert!(!changed);
+        assert_eq!(set.items.len(), 6);
+        assert_eq!(server.not_modified(), 1);
+
+        assert!(!source.revalidate_home(&client, &mut revalidation).await.unwrap().1);
+        let home = std::fs::read_to_string("src/home.json").unwrap();
+        server.serve("/home.json", home.replacen("New to Disney+", "Just Added", 1));
+        assert!(source.revalidate_home(&client, &mut revalidation).await.unwrap().1);
+        assert_eq!(server.not_modified(), 1);
+    }
+}
@@ -0,0 +1,19 @@
+//! Portcullis, a streaming service home screen.
+//!
+//! The loaders, the layout and the navigation logic have no windowing
+//! dependency, only the `portcullis` binary needs SDL.
+//!
+//! Functions taking a `glow::Context` are `unsafe` because they issue GL calls:
+//! the context must be current on the calling thread and every GL object passed
+//! in must belong to it.
+
+#![allow(clippy::missing_safety_doc)] // Covered once above rather than on every GL function
+
+pub mod assets;
+pub mod config;
+pub mod content;
+pub mod render;
+pub mod shaders;
+#[cfg(test)]
+mod test_support;
+pub mod ui;
@@ -0,0 +1,67 @@
+#[derive(Default)]
+pub struct Camera2D
+{
+    pub position: glam::Vec2,
+    pub viewport: glam::Vec2,
+}
+
+impl Camera2D
+{
+    pub fn new() -> Self
+    {
+        Self { position: glam::Vec2::ZERO, viewport: glam::Vec2::ZERO }
+    }
+
+    pub fn update_viewport_dimensions(&mut self, window_width: f32, window_height: f32)
+    {
+        self.viewport = glam::vec2(window_width, window_height);
+    }
+
+    /// Useful for drawing items that should not move along with navigation.
+    pub fn get_origin_matrix(&self) -> glam::Mat4
+    {
+        glam::f32::Mat4::orthographic_rh(0.0, self.viewport.x, self.viewport.y, 0.0, -1.0, 1.0)
+    }
+
+    /// Useful for drawing items that should move along with navigation.
+    pub fn get_matrix(&self) -> glam::Mat4
+    {
+        glam::f32::Mat4::orthographic_rh(
+            self.position.x,
+            self.position.x + self.viewport.x,
+            self.position.y + self.viewport.y,
+            self.position.y,
+            -1.0,
+            1.0,
+        )
+    }
+
+    pub fn get_position_in_screen_space(&self, position: glam::Vec2) -> glam::Vec2
+    {
+        position - self.position
+    }
+
+    pub fn is_rectangle_in_view(&self, position: glam::Vec2, dimensions: glam::Vec2) -> bool
+    {
+        position.x <= (self.position.x + self.viewport.x)
+            && (position.x + dimensions.x) >= self.position.x
+            && position.y <= (self.position.y + self.viewport.y)
+            && (position.y + dimensions.y) >= self.position.y
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
+    fn test()
+    {
+        let camera = Camera2D { position: glam::vec2(0.0, 0.0), viewport: glam::vec2(256.0, 256.0) };
+
+        assert_eq!(camera.is_rectangle_in_view(glam::vec2(0.0, 0.0), glam::vec2(64.0, 64.0)), true);
+        assert_eq!(camera.is_rectangle_in_view(glam::vec2(1000.0, 1000.0), glam::vec2(64.0, 64.0)), false);
+    }
+}
@@ -0,0 +1,6 @@
+//! OpenGL drawing: the camera, textured quads, text and the rows themselves.
+
+pub mod camera;
+pub mod quad;
+pub mod rows;
+pub mod text;
@@ -0,0 +1,99 @@
+use glow::*;
+
+pub unsafe fn draw_quad(
+    gl: &Context,
+    program: NativeProgram,
+    position: glam::Vec2,
+    dimensions: glam::Vec2,
+    color: glam::Vec4,
+    orthographic_projection_matrix: glam::Mat4,
+)
+{
+    let rectangle_color = gl.get_uniform_location(program, "rectangle_color").unwrap();
+    gl.uniform_4_f32(Some(&rectangle_color), color.x, color.y, color.z, color.w);
+
+    let rectangle_position = gl.get_uniform_location(program, "rectangle_position").unwrap();
+    gl.uniform_2_f32(Some(&rectangle_position), position.x + dimensions.x / 2.0, position.y + dimensions.y / 2.0);
+
+    let rectangle_dimensions = gl.get_uniform_location(program, "rectangle_dimensions").unwrap();
+    gl.uniform_2_f32(Some(&rectangle_dimensions), dimensions.x, dimensions.y);
+
+    let orthographic_projection = gl.get_uniform_location(program, "orthographic_projection").unwrap();
+    gl.uniform_matrix_4_f32_slice(
+        Some(&orthographic_projection),
+        false,
+        &orthographic_projection_matrix.to_cols_array(),
+    );
+
+    gl.draw_arrays(glow::QUADS, 0, 4);
+}
+
+pub unsafe fn draw_quad_textured(
+    gl: &Context,
+    program: NativeProgram,
+    position: glam::Vec2,
+    dimensions: glam::Vec2,
+    color: glam::Vec4,
+    orthographic_projection_matrix: glam::Mat4,
+    texture: NativeTexture,
+)
+{
+    gl.active_texture(glow::TEXTURE0);
+    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+
+    gl.enable(glow::BLEND);
+    gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+
+    let using_rectangle_texture = gl.get_uniform_location(program, "using_rectangle_texture").unwrap();
+    gl.uniform_1_u32(Some(&using_rectangle_texture), 1);
+
+    draw_quad(gl, program, position, dimensions, color, orthographic_projection_matrix);
+
+    let using_rectangle_texture = gl.get_uniform_location(program, "using_rectangle_texture").unwrap();
+    gl.uniform_1_u32(Some(&using_rectangle_texture), 0);
+
+    gl.bind_texture(glow::TEXTURE_2D, None);
+}
+
+pub unsafe fn draw_image_centered(
+    gl: &Context,
+    program: NativeProgram,
+    position: glam::Vec2,
+    dimensions: glam::Vec2,
+    color: glam::Vec4,
+    orthographic_projection_matrix: glam::Mat4,
+    texture: NativeTexture,
+)
+{
+    gl.active_texture(glow::TEXTURE0);
+    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+
+    gl.enable(glow::BLEND);
+    gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+
+    let using_rectangle_texture = gl.get_uniform_location(program, "using_rectangle_texture").unwrap();
+    gl.uniform_1_u32(Some(&using_rectangle_texture), 1);
+
+    let rectangle_color = gl.get_uniform_location(program, "rectangle_color").unwrap();
+    gl.uniform_4_f32(Some(&rectangle_color), color.x, color.y, color.z, color.w);
+
+    let rectangle_position = gl.get_uniform_location(program, "rectangle_position").unwrap();
+    gl.uniform_2_f32(Some(&rectangle_position), position.x, position.y);
+
+    let rectangle_dimensions = gl.get_uniform_location(program, "rectangle_dimensions").unwrap();
+    gl.uniform_2_f32(Some(&rectangle_dimensions), dimensions.x, dimensions.y);
+
+    let orthographic_projection = gl.get_uniform_location(program, "orthographic_projection").unwrap();
+    gl.uniform_matrix_4_f32_slice(
+        Some(&orthographic_projection),
+        false,
+        &orthographic_projection_matrix.to_cols_array(),
+    );
+
+    gl.draw_arrays(glow::QUADS, 0, 4);
+
+    let using_rectangle_texture = gl.get_uniform_location(program, "using_rectangle_texture").unwrap();
+    gl.uniform_1_u32(Some(&using_rectangle_texture), 0);
+
+    gl.bind_texture(glow::TEXTURE_2D, None);
+}
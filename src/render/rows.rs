@@ -0,0 +1,236 @@
+use glow::*;
+use glow_glyph::{Section, Text};
+
+use crate::assets::images::{ImageLoader, Priority};
+use crate::assets::textures::TextureManager;
+use crate::content::collections::RowState;
+use crate::render::camera::Camera2D;
+use crate::render::quad::{draw_quad, draw_quad_textured};
+use crate::ui::app::AppState;
+use crate::ui::layout::Rect;
+use crate::ui::navigation::{focused_slot, Edges};
+
+/// Draws the rows of `state`, requesting the artwork of tiles on screen or
+/// about to be.
+pub unsafe fn draw_all_collections(
+    state: &AppState,
+    gl: &Context,
+    program: NativeProgram,
+    glyph_brush: &mut glow_glyph::GlyphBrush,
+    spinners: &mut Vec<glam::Vec2>,
+    textures: &mut TextureManager,
+    images: &mut ImageLoader,
+)
+{
+    let Some(ref collections) = state.collections
+    else
+    {
+        return;
+    };
+
+    let (camera, selection, travel) = (&state.camera, state.selection, state.travel);
+    let layout = state.tile_layout();
+    let repeating = state.navigation.columns == Edges::Carousel;
+
+    // Prefetch one screen ahead in the direction the user last moved
+    let ahead = Camera2D { position: camera.position + travel * camera.viewport, viewport: camera.viewport };
+
+    for (row, collection) in collections.iter().enumerate()
+    {
+        let title = collection.name.as_str();
+        let title_section = Section {
+            screen_position: camera.get_position_in_screen_space(layout.title_position(row)).into(),
+            bounds: camera.viewport.into(),
+            text: vec![Text::default().with_text(title).with_color([1.0, 1.0, 1.0, 1.0]).with_scale(layout.title_size)],
+            ..Section::default()
+        };
+
+        glyph_brush.queue(title_section);
+
+        let row_selected = row as i32 == selection.y as i32;
+
+        if collection.state == RowState::Loading
+        {
+            for slot in 0 .. layout.columns as i32 + 1
+            {
+                let tile = layout.tile_rect(row, slot, 0.0);
+
+                if !camera.is_rectangle_in_view(tile.position, tile.size)
+                {
+                    continue;
+                }
+
+                if row_selected && slot == 0
+                {
+                    let border = tile.grown(layout.border);
+                    draw_quad(
+                        gl,
+                        program,
+                        border.position,
+                        border.size,
+                        glam::vec4(1.0, 1.0, 1.0, 0.75),
+                        camera.get_matrix(),
+                    );
+                }
+
+                let color = glam::vec4(0.227, 0.227, 0.258, 0.5);
+                draw_quad(gl, program, tile.position, tile.size, color, camera.get_matrix());
+            }
+
+            continue;
+        }
+
+        if let RowState::Failed(ref error) = collection.state
+        {
+            let banner = layout.banner_rect(row);
+            let (position, dimensions) = (banner.position, banner.size);
+
+            if camera.is_rectangle_in_view(position, dimensions)
+            {
+                let alpha = if row_selected { 0.75 } else { 0.5 };
+                let color = glam::vec4(0.227, 0.227, 0.258, alpha);
+                draw_quad(gl, program, position, dimensions, color, camera.get_matrix());
+
+                glyph_brush.queue(Section {
+                    screen_position: camera.get_position_in_screen_space(position + 24.0 * layout.scale).into(),
+                    bounds: dimensions.into(),
+                    text: vec![Text::default()
+                        .with_text(&format!("Couldn't load this row: {}", error))
+                        .with_color([1.0, 1.0, 1.0, 0.75])
+                        .with_scale(20.0 * layout.scale)],
+                    ..Section::default()
+                });
+            }
+
+            continue;
+        }
+
+        // Carousels draw whichever copies of their tiles are on screen, with some to spare for prefetching
+        let count = collection.videos.len() as i32;
+        let slots = if repeating && count > 0
+        {
+            let first = collection.scroll.floor() as i32 - layout.columns as i32;
+            first .. first + layout.columns as i32 * 3 + 1
+        }
+        else
+        {
+            0 .. count
+        };
+        let focused = focused_slot(collection, layout.columns, state.navigation.columns);
+
+        for slot in slots
+        {
+            let video = &collection.videos[slot.rem_euclid(count) as usize];
+            let selected = row_selected && slot == focused;
+            let tile = layout.tile_rect(row, slot, collection.scroll);
+            let (position, dimensions) = (tile.position, tile.size);
+            let in_view = camera.is_rectangle_in_view(position, dimensions);
+
+            // Only the selected row scrolls horizontally, every row scrolls vertically
+            let is_ahead = (travel.y != 0.0 || row_selected) && ahead.is_rectangle_in_view(position, dimensions);
+
+            let priority = if selected
+            {
+                Some(Priority::Focused)
+            }
+            else if in_view
+            {
+                Some(Priority::Visible)
+            }
+            else if is_ahead
+            {
+                Some(Priority::Ahead)
+            }
+            else
+            {
+                None
+            };
+
+            if let Some(priority) = priority
+            {
+                if !textures.contains(&video.tile.url)
+                {
+                    images.request(&video.tile, priority);
+                }
+            }
+
+            if in_view
+            {
+                // The focused tile grows around its centre, and bumps against clamped edges
+                let tile = if selected
+                {
+                    let focused = tile.scaled(state.focus_scale);
+                    Rect { position: focused.position + state.focus_nudge * layout.scale, ..focused }
+                }
+                else
+                {
+                    tile
+                };
+                let (position, dimensions) = (tile.position, tile.size);
+
+                if selected
+                {
+                    let border = tile.grown(layout.border);
+                    draw_quad(
+                        gl,
+                        program,
+                        border.position,
+                        border.size,
+                        glam::vec4(1.0, 1.0, 1.0, 0.75),
+                        camera.get_matrix(),
+                    );
+                }
+
+                // Artwork replaced after a resize stays up until the new one arrives
+                let texture = textures
+                    .get(&video.tile.url)
+                    .or_else(|| video.previous_tile.as_ref().and_then(|tile| textures.get(&tile.url)));
+
+                if let Some(texture) = texture
+                {
+                    draw_quad_textured(
+                        gl,
+                        program,
+                        position,
+                        dimensions,
+                        glam::vec4(1.0, 1.0, 1.0, 1.0),
+                        camera.get_matrix(),
+                        texture,
+                    );
+                }
+                else
+                {
+                    draw_quad(
+                        gl,
+                        program,
+                        position,
+                        dimensions,
+                        glam::vec4(0.227, 0.227, 0.258, 0.5),
+                        camera.get_matrix(),
+                    );
+
+                    let failure = images.failure(&video.tile);
+
+                    // Tiles that were given up on stay blank, the rest are still on their way
+                    if failure.is_none_or(|failure| failure.retry_at.is_some())
+                    {
+                        spinners.push(tile.centre());
+                    }
+
+                    if let Some(failure) = failure.filter(|_| cfg!(debug_assertions))
+                    {
+                        glyph_brush.queue(Section {
+                            screen_position: camera.get_position_in_screen_space(position + 8.0 * layout.scale).into(),
+                            bounds: dimensions.into(),
+                            text: vec![Text::default()
+                                .with_text(&format!("Attempt {}: {}", failure.attempts, failure.reason))
+                                .with_color([1.0, 0.5, 0.5, 1.0])
+                                .with_scale(12.0 * layout.scale)],
+                            ..Section::default()
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,11 @@
+use glow::Context;
+use glow_glyph::{ab_glyph, GlyphBrush, GlyphBrushBuilder};
+
+/// A glyph brush for the bundled Roboto font, used for every piece of text.
+pub fn glyph_brush(gl: &Context) -> GlyphBrush
+{
+    let font = ab_glyph::FontArc::try_from_slice(include_bytes!("../../res/font/Roboto/Roboto-Regular.ttf"))
+        .expect("Failed to load font");
+
+    GlyphBrushBuilder::using_font(font).build(gl)
+}
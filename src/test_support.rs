@@ -0,0 +1,359 @@
+//! An in-process content server on localhost, so the loading pipeline can be
+//! tested without touching the real CDN.
+//!
+//! It serves the bundled `home.json`, a synthetic `sets/{refId}.json` for any
+//! `refId` and a generated JPEG for any `images/{masterId}.jpg`. JSON responses
+//! carry an `ETag` and honour `If-None-Match`. Faults and latency can be
+//! injected per path while the server is running.
+//!
+//! The rows and tiles shared by the unit tests are built here too.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::content::collections::{Collection, RowState, Video, CONTENT_NOT_SET};
+use crate::content::model;
+
+const HOME: &str = include_str!("home.json");
+const TILES_PER_SET: usize = 6;
+
+/// What to do instead of answering normally.
+#[derive(Clone, Copy, Debug)]
+pub enum Fault
+{
+    /// `500 Internal Server Error`.
+    ServerError,
+
+    /// `404 Not Found`.
+    NotFound,
+
+    /// The headers announce the whole body but only half of it is sent.
+    Truncated,
+
+    /// A complete response whose JSON is cut in half.
+    MalformedJson,
+
+    /// A complete response sent in eight pieces, `TRICKLE_PAUSE` apart.
+    Trickle,
+}
+
+pub const TRICKLE_PAUSE: Duration = Duration::from_millis(50);
+
+#[derive(Default)]
+struct State
+{
+    latency: Duration,
+    faults: HashMap<String, Fault>,
+    overrides: HashMap<String, Vec<u8>>,
+    hits: HashMap<String, usize>,
+    not_modified: usize,
+}
+
+pub struct MockServer
+{
+    pub base_url: String,
+    state: Arc<Mutex<State>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl MockServer
+{
+    pub async fn start() -> Self
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind the mock server");
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let task = {
+            let (base_url, state) = (base_url.clone(), state.clone());
+
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await
+                {
+                    tokio::spawn(respond(stream, base_url.clone(), state.clone()));
+                }
+            })
+        };
+
+        Self { base_url, state, task }
+    }
+
+    /// Delays every response from now on.
+    pub fn set_latency(&self, latency: Duration)
+    {
+        self.state.lock().unwrap().latency = latency;
+    }
+
+    /// Makes every request for `path` (e.g. `/home.json`) fail with `fault`.
+    pub fn fail(&self, path: &str, fault: Fault)
+    {
+        self.state.lock().unwrap().faults.insert(path.to_owned(), fault);
+    }
+
+    /// Serves `body` for `path` instead of the generated content.
+    pub fn serve(&self, path: &str, body: impl Into<Vec<u8>>)
+    {
+        self.state.lock().unwrap().overrides.insert(path.to_owned(), body.into());
+    }
+
+    /// How many requests were made for `path`.
+    pub fn hits(&self, path: &str) -> usize
+    {
+        self.state.lock().unwrap().hits.get(path).copied().unwrap_or(0)
+    }
+
+    /// How many requests were answered with `304 Not Modified`.
+    pub fn not_modified(&self) -> usize
+    {
+        self.state.lock().unwrap().not_modified
+    }
+
+    /// A tile whose artwork is served by this server.
+    pub fn tile(&self, master_id: &str) -> model::ImageDetails
+    {
+        tile(&self.base_url, master_id)
+    }
+}
+
+impl Drop for MockServer
+{
+    fn drop(&mut self)
+    {
+        self.task.abort();
+    }
+}
+
+/// Artwork for `master_id` at the URL this server would serve it from.
+pub fn tile(base_url: &str, master_id: &str) -> model::ImageDetails
+{
+    model::ImageDetails {
+        master_id: master_id.to_owned(),
+        master_width: 64,
+        master_height: 36,
+        url: format!("{}/images/{}.jpg?width=500", base_url, master_id),
+    }
+}
+
+/// A ready row with a video for each of `content_ids`, named after them. Every
+/// video has poster, wide and banner artwork and shows the wide one.
+pub fn row(set_id: &str, content_ids: impl IntoIterator<Item = impl ToString>) -> Collection
+{
+    let videos = content_ids
+        .into_iter()
+        .map(|content_id| {
+            let content_id = content_id.to_string();
+            let variant = |aspect_ratio: f32, shape: &str| model::TileVariant {
+                aspect_ratio,
+                image: model::ImageDetails {
+                    master_id: format!("{}-{}", content_id, shape),
+                    master_width: 1000,
+                    master_height: (1000.0 / aspect_ratio) as u32,
+                    url: format!("http://localhost/{}/{}?width=500", content_id, shape),
+                },
+            };
+            let variants = vec![variant(0.71, "poster"), variant(1.78, "wide"), variant(3.91, "banner")];
+
+            Video {
+                name: content_id.clone(),
+                content_id,
+                tile: variants[1].image.clone(),
+                variants,
+                previous_tile: None,
+                content_index: CONTENT_NOT_SET,
+            }
+        })
+        .collect();
+
+    Collection {
+        container_index: 0,
+        set_id: set_id.to_owned(),
+        name: set_id.to_owned(),
+        videos,
+        selected_video: 0,
+        scroll: 0.0,
+        state: RowState::Ready,
+        skipped: Vec::new(),
+    }
+}
+
+async fn respond(mut stream: TcpStream, base_url: String, state: Arc<Mutex<State>>)
+{
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+
+    while !request.windows(4).any(|window| window == b"\r\n\r\n")
+    {
+        match stream.read(&mut buffer).await
+        {
+            Ok(0) | Err(_) => return,
+            Ok(read) => request.extend_from_slice(&buffer[.. read]),
+        }
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let target = request.split_whitespace().nth(1).unwrap_or("/");
+    let path = target.split('?').next().unwrap_or(target).to_owned();
+    let if_none_match = request.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.eq_ignore_ascii_case("if-none-match").then(|| value.trim().to_owned())
+    });
+
+    let (latency, fault, body) = {
+        let mut state = state.lock().unwrap();
+        *state.hits.entry(path.clone()).or_default() += 1;
+        (state.latency, state.faults.get(&path).copied(), state.overrides.get(&path).cloned())
+    };
+
+    tokio::time::sleep(latency).await;
+
+    let body = body.or_else(|| generate(&base_url, &path));
+    let content_type = if path.ends_with(".jpg") { "image/jpeg" } else { "application/json" };
+
+    let response = match (fault, body)
+    {
+        (Some(Fault::ServerError), _) => head("500 Internal Server Error", "text/plain", 0, None),
+        (Some(Fault::NotFound), _) | (_, None) => head("404 Not Found", "text/plain", 0, None),
+
+        (Some(Fault::Truncated), Some(body)) =>
+        {
+            let mut response = head("200 OK", content_type, body.len(), None);
+            response.extend_from_slice(&body[.. body.len() / 2]);
+            response
+        }
+
+        (Some(Fault::MalformedJson), Some(body)) =>
+        {
+            let body = &body[.. body.len() / 2];
+            let mut response = head("200 OK", content_type, body.len(), None);
+            response.extend_from_slice(body);
+            response
+        }
+
+        (None | Some(Fault::Trickle), Some(body)) =>
+        {
+            let etag = etag(&body);
+
+            if if_none_match.as_deref() == Some(etag.as_str())
+            {
+                state.lock().unwrap().not_modified += 1;
+                head("304 Not Modified", content_type, 0, Some(&etag))
+            }
+            else
+            {
+                let mut response = head("200 OK", content_type, body.len(), Some(&etag));
+                response.extend_from_slice(&body);
+                response
+            }
+        }
+    };
+
+    if let Some(Fault::Trickle) = fault
+    {
+        for piece in response.chunks(response.len().div_ceil(8))
+        {
+            if stream.write_all(piece).await.is_err()
+            {
+                return;
+            }
+
+            let _ = stream.flush().await;
+            tokio::time::sleep(TRICKLE_PAUSE).await;
+        }
+    }
+    else
+    {
+        let _ = stream.write_all(&response).await;
+    }
+
+    let _ = stream.shutdown().await;
+}
+
+fn head(status: &str, content_type: &str, content_length: usize, etag: Option<&str>) -> Vec<u8>
+{
+    let etag = etag.map(|etag| format!("ETag: {}\r\n", etag)).unwrap_or_default();
+
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+        status, content_type, content_length, etag
+    )
+    .into_bytes()
+}
+
+fn etag(body: &[u8]) -> String
+{
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("\"{:x}\"", hasher.finish())
+}
+
+fn generate(base_url: &str, path: &str) -> Option<Vec<u8>>
+{
+    if path == "/home.json"
+    {
+        return Some(HOME.as_bytes().to_vec());
+    }
+
+    if let Some(ref_id) = path.strip_prefix("/sets/").and_then(|file| file.strip_suffix(".json"))
+    {
+        return Some(set(base_url, ref_id).to_string().into_bytes());
+    }
+
+    let master_id = path.strip_prefix("/images/")?.strip_suffix(".jpg")?;
+    Some(jpeg(master_id))
+}
+
+/// A `CuratedSet` of `DmcVideo` items whose artwork points back at the server.
+fn set(base_url: &str, ref_id: &str) -> serde_json::Value
+{
+    let items: Vec<_> = (0 .. TILES_PER_SET)
+        .map(|index| {
+            let content_id = format!("{}-{}", ref_id, index);
+            let tile = tile(base_url, &content_id);
+
+            json!({
+                "type": "DmcVideo",
+                "contentId": content_id,
+                "text": { "title": { "full": { "program": { "default": { "content": format!("Video {}", index) } } } } },
+                "image": { "tile": { "1.78": { "program": { "default": {
+                    "masterId": tile.master_id,
+                    "masterWidth": tile.master_width,
+                    "masterHeight": tile.master_height,
+                    "url": tile.url,
+                } } } } },
+            })
+        })
+        .collect();
+
+    json!({
+        "data": {
+            "CuratedSet": {
+                "setId": ref_id,
+                "contentClass": "editorial",
+                "type": "CuratedSet",
+                "text": { "title": { "full": { "set": { "default": { "content": format!("Set {}", ref_id) } } } } },
+                "items": items,
+            }
+        }
+    })
+}
+
+/// A small gradient, different for every `masterId`.
+fn jpeg(master_id: &str) -> Vec<u8>
+{
+    let seed = master_id.bytes().fold(0u8, |seed, byte| seed.wrapping_add(byte));
+    let image = image::RgbImage::from_fn(64, 36, |x, y| image::Rgb([x as u8 * 4, y as u8 * 7, seed]));
+
+    let mut jpeg = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgb8(image)
+        .write_to(&mut jpeg, image::ImageOutputFormat::Jpeg(80))
+        .expect("Encoding to memory cannot fail");
+
+    jpeg.into_inner()
+}
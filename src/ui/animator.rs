@@ -0,0 +1,243 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+use keyframe::{AnimationSequence, CanTween};
+
+/// Runs the animations of a context `C` (usually the whole UI state). Each
+/// animated property is named by a key `K` and can be of any tweenable type.
+///
+/// Properties animate in parallel, while each one plays its own queue of
+/// tweens and completion callbacks in sequence. A tween writes every new value
+/// into the context through the setter it was started with, so the animated
+/// fields stay plain fields everywhere else.
+pub struct Animator<K, C>
+{
+    tracks: HashMap<K, VecDeque<Step<C>>>,
+}
+
+enum Step<C>
+{
+    Tween(Box<dyn Tween<C>>),
+    Callback(Box<dyn FnOnce(&mut C)>),
+}
+
+/// An `AnimationSequence` with its setter, whatever the type it tweens.
+trait Tween<C>
+{
+    /// Moves on by `dt` seconds and returns the time left over past the end.
+    fn advance(&mut self, dt: f64) -> f64;
+    fn apply(&self, context: &mut C);
+    fn finished(&self) -> bool;
+}
+
+struct Sequence<T, F>
+{
+    sequence: AnimationSequence<T>,
+    setter: F,
+}
+
+impl<T, F, C> Tween<C> for Sequence<T, F>
+where
+    T: CanTween + Copy + Default,
+    F: Fn(&mut C, T),
+{
+    fn advance(&mut self, dt: f64) -> f64
+    {
+        self.sequence.advance_by(dt)
+    }
+
+    fn apply(&self, context: &mut C)
+    {
+        (self.setter)(context, self.sequence.now());
+    }
+
+    fn finished(&self) -> bool
+    {
+        self.sequence.finished()
+    }
+}
+
+impl<K, C> Default for Animator<K, C>
+{
+    fn default() -> Self
+    {
+        Self { tracks: HashMap::new() }
+    }
+}
+
+impl<K: Copy + Eq + Hash, C> Animator<K, C>
+{
+    /// Starts animating `key`, cancelling whatever it was doing.
+    pub fn play<T>(&mut self, key: K, sequence: AnimationSequence<T>, setter: impl Fn(&mut C, T) + 'static)
+    where
+        T: CanTween + Copy + Default + 'static,
+    {
+        self.cancel(key);
+        self.queue(key, sequence, setter);
+    }
+
+    /// Animates `key` once everything already queued on it has finished.
+    pub fn queue<T>(&mut self, key: K, sequence: AnimationSequence<T>, setter: impl Fn(&mut C, T) + 'static)
+    where
+        T: CanTween + Copy + Default + 'static,
+    {
+        let tween = Sequence { sequence, setter };
+        self.tracks.entry(key).or_default().push_back(Step::Tween(Box::new(tween)));
+    }
+
+    /// Calls `callback` once everything already queued on `key` has finished.
+    /// Cancelling `key` before then drops it uncalled.
+    pub fn on_complete(&mut self, key: K, callback: impl FnOnce(&mut C) + 'static)
+    {
+        self.tracks.entry(key).or_default().push_back(Step::Callback(Box::new(callback)));
+    }
+
+    /// Stops `key` where it is.
+    pub fn cancel(&mut self, key: K)
+    {
+        self.tracks.remove(&key);
+    }
+
+    pub fn cancel_where(&mut self, mut cancel: impl FnMut(&K) -> bool)
+    {
+        self.tracks.retain(|key, _| !cancel(key));
+    }
+
+    pub fn is_animating(&self, key: K) -> bool
+    {
+        self.tracks.contains_key(&key)
+    }
+
+    pub fn is_idle(&self) -> bool
+    {
+        self.tracks.is_empty()
+    }
+
+    /// Moves every property on by `dt` seconds. Time left over when a tween
+    /// ends goes to the next one in its queue.
+    pub fn advance(&mut self, dt: f32, context: &mut C)
+    {
+        self.tracks.retain(|_, steps| {
+            let mut remaining = dt as f64;
+
+            while let Some(step) = steps.pop_front()
+            {
+                match step
+                {
+                    Step::Callback(callback) => callback(context),
+
+                    Step::Tween(mut tween) =>
+                    {
+                        remaining = tween.advance(remaining);
+                        tween.apply(context);
+
+                        if !tween.finished()
+                        {
+                            steps.push_front(Step::Tween(tween));
+                            break;
+                        }
+                    }
+                }
+            }
+
+            !steps.is_empty()
+        });
+    }
+
+    /// Takes over the animations started on `other`, replacing this one's for
+    /// the same keys. For an animator that lives inside its own context: it is
+    /// swapped out to advance, and callbacks start anything new on the
+    /// stand-in left behind.
+    pub fn absorb(&mut self, other: Self)
+    {
+        self.tracks.extend(other.tracks);
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use keyframe::{functions, keyframes};
+
+    use super::*;
+    use crate::ui::tween::V2;
+
+    #[derive(Default)]
+    struct Scene
+    {
+        size: f32,
+        position: glam::Vec2,
+        log: Rc<RefCell<Vec<&'static str>>>,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+    enum Key
+    {
+        Size,
+        Position,
+    }
+
+    fn ramp(from: f32, to: f32) -> AnimationSequence<f32>
+    {
+        keyframes![(from, 0.0, functions::Linear), (to, 1.0, functions::Linear)]
+    }
+
+    #[test]
+    fn properties_run_in_parallel_and_queues_in_sequence()
+    {
+        let mut animator = Animator::default();
+        let mut scene = Scene::default();
+        let log = scene.log.clone();
+
+        animator.play(Key::Size, ramp(0.0, 1.0), |scene: &mut Scene, size| scene.size = size);
+        animator.queue(Key::Size, ramp(1.0, 3.0), |scene: &mut Scene, size| scene.size = size);
+        animator.on_complete(Key::Size, |scene: &mut Scene| scene.log.borrow_mut().push("size"));
+
+        let path = keyframes![(V2(0.0, 0.0), 0.0, functions::Linear), (V2(4.0, 2.0), 2.0, functions::Linear)];
+        animator.play(Key::Position, path, |scene: &mut Scene, position: V2| scene.position = position.into());
+
+        animator.advance(0.5, &mut scene);
+        assert_eq!(scene.size, 0.5);
+        assert_eq!(scene.position, glam::vec2(1.0, 0.5));
+
+        // Time past the end of the first tween carries into the second
+        animator.advance(1.0, &mut scene);
+        assert_eq!(scene.size, 2.0);
+        assert!(log.borrow().is_empty());
+
+        animator.advance(0.5, &mut scene);
+        assert_eq!(scene.size, 3.0);
+        assert_eq!(scene.position, glam::vec2(4.0, 2.0));
+        assert_eq!(*log.borrow(), ["size"]);
+
+        animator.advance(0.1, &mut scene);
+        assert!(animator.is_idle());
+    }
+
+    #[test]
+    fn cancelled_or_replaced_animations_stop_where_they_are()
+    {
+        let mut animator = Animator::default();
+        let mut scene = Scene::default();
+        let log = scene.log.clone();
+
+        animator.play(Key::Size, ramp(0.0, 1.0), |scene: &mut Scene, size| scene.size = size);
+        animator.on_complete(Key::Size, |scene: &mut Scene| scene.log.borrow_mut().push("first"));
+        animator.advance(0.25, &mut scene);
+
+        // Replacing drops the old callback, the new tween starts from wherever it is told to
+        animator.play(Key::Size, ramp(scene.size, 2.0), |scene: &mut Scene, size| scene.size = size);
+        animator.on_complete(Key::Size, |scene: &mut Scene| scene.log.borrow_mut().push("second"));
+        animator.advance(0.5, &mut scene);
+        assert_eq!(scene.size, 1.125);
+
+        animator.cancel(Key::Size);
+        animator.advance(1.0, &mut scene);
+        assert_eq!(scene.size, 1.125);
+        assert!(!animator.is_animating(Key::Size));
+        assert!(log.borrow().is_empty());
+    }
+}
@@ -0,0 +1,1227 @@
+use std::collections::HashMap;
+
+use keyframe::{functions, keyframes, AnimationSequence, CanTween};
+use serde::Deserialize;
+
+use crate::content::collections::{merge_collections, Collection, ContentUpdate, CONTENT_NOT_SET};
+use crate::render::camera::Camera2D;
+use crate::ui::animator::Animator;
+use crate::ui::layout::{LayoutSettings, TileLayout};
+use crate::ui::navigation::{self, focused_slot, selected_row_has_videos, Edges, Navigation};
+use crate::ui::tween::V2;
+
+/// What the user asked for, whatever the input device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Action
+{
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Activate, // Play the focused tile, or retry loading
+    Back,     // Close the playing content, or leave the app
+    Quit,
+}
+
+/// A mouse, or a finger through the mouse events SDL makes from touches.
+/// Positions are in window pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pointer
+{
+    Hover(glam::Vec2), // Moved with no button held
+    Press(glam::Vec2),
+    Drag(glam::Vec2), // Moved with the button held
+    Release(glam::Vec2),
+    Wheel(glam::Vec2), // Wheel or trackpad steps, positive is right and up
+}
+
+/// A press that can still turn out to be a click or a drag along a row.
+struct Drag
+{
+    row: usize,
+    start: glam::Vec2,
+    last: glam::Vec2,
+    moved: bool,   // Went further than `DRAG_THRESHOLD`, releasing won't click
+    pending: f32,  // Columns scrolled since the last `advance`
+    velocity: f32, // Columns per second, smoothed over frames
+}
+
+/// How far a press can wander, in pixels, and still be a click.
+const DRAG_THRESHOLD: f32 = 12.0;
+
+/// How quickly a flung row slows down, per second, and where it stops.
+const FLING_FRICTION: f32 = 4.0;
+const FLING_STOP: f32 = 0.05;
+
+/// Columns per second added by each wheel step.
+const WHEEL_SPEED: f32 = 3.0;
+
+/// Length of a move from rest, and of one that redirects a move in progress.
+const MOVE_SECS: f32 = 0.5;
+const RETARGET_SECS: f32 = 0.3;
+
+/// How long an action that arrives mid-animation waits for it to end.
+const BUFFER_SECS: f32 = MOVE_SECS + 0.1;
+
+/// How far the tile shape can drift, as a fraction of its aspect ratio, before
+/// artwork of another shape is picked.
+const RESHAPE_THRESHOLD: f32 = 0.1;
+
+/// Artwork widths are rounded up to this many pixels, so that resizing the
+/// window only refetches tiles once in a while.
+const ARTWORK_WIDTH_STEP: f32 = 100.0;
+
+/// What `AppState` animates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Property
+{
+    Camera,
+    RowScroll(usize), // Index of the row
+    ContentSize,
+    FocusScale,
+    FocusNudge,
+}
+
+/// Work `update` leaves to the caller because it involves I/O.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effect
+{
+    /// Load home.json again, the retry screen was dismissed.
+    Reload,
+}
+
+/// Everything the home screen needs to know to draw a frame, and the rules for
+/// changing it. Free of SDL and GL so it can be driven headlessly.
+pub struct AppState
+{
+    pub running: bool,
+    pub collections: Option<Vec<Collection>>,
+    pub load_error: Option<String>, // Shows the retry screen when home.json can't be loaded
+    pub camera: Camera2D,
+    pub selection: glam::Vec2,          // Focused column and row
+    pub travel: glam::Vec2,             // Direction of the last move, used to prefetch artwork
+    pub showing_content: Option<usize>, // Index of the content shader being played
+    pub content_size: f32,              // 0 is closed, 1 is full screen
+    content_target: f32,                // Size the content is opening or closing to
+    pub focus_scale: f32,               // Size of the focused tile, it grows a little when focused
+    pub focus_nudge: glam::Vec2,        // Offset of the focused tile, it bumps against clamped edges
+    pub navigation: Navigation,
+    pub layout: LayoutSettings,
+    pointer: Option<glam::Vec2>, // Last known position, the wheel scrolls the row under it
+    drag: Option<Drag>,
+    fling: Option<(usize, f32)>, // Row coasting after a drag or wheel, and its speed in columns per second
+    buffered: Option<(Action, f32)>, // Waiting for an animation to finish, and for how long so far
+    content_count: usize,
+    next_content: usize,
+    animator: Animator<Property, AppState>,
+    scroll_targets: HashMap<usize, f32>, // Where each row still scrolling is headed, by row
+    artwork: Option<(f32, u32)>,         // Aspect ratio and width tiles were picked for
+}
+
+impl AppState
+{
+    /// `content_count` is how many content shaders tiles can be assigned.
+    pub fn new(viewport: glam::Vec2, content_count: usize) -> Self
+    {
+        let mut camera = Camera2D::new();
+        camera.update_viewport_dimensions(viewport.x, viewport.y);
+
+        Self {
+            running: true,
+            collections: None,
+            load_error: None,
+            camera,
+            selection: glam::Vec2::ZERO,
+            travel: glam::Vec2::Y,
+            showing_content: None,
+            content_size: 0.0,
+            content_target: 0.0,
+            focus_scale: 1.0,
+            focus_nudge: glam::Vec2::ZERO,
+            navigation: Navigation::default(),
+            layout: LayoutSettings::default(),
+            pointer: None,
+            drag: None,
+            fling: None,
+            buffered: None,
+            content_count,
+            next_content: 0,
+            animator: Animator::default(),
+            scroll_targets: HashMap::new(),
+            artwork: None,
+        }
+    }
+
+    /// Where everything goes on the current viewport.
+    pub fn tile_layout(&self) -> TileLayout
+    {
+        TileLayout::new(self.camera.viewport, &self.layout)
+    }
+
+    /// Lays the rows out again for a new window size, keeping the focused row
+    /// at the top and every row within its new scroll range.
+    pub fn resize(&mut self, viewport: glam::Vec2)
+    {
+        self.camera.update_viewport_dimensions(viewport.x, viewport.y);
+
+        let layout = self.tile_layout();
+        self.animator.cancel(Property::Camera);
+        self.camera.position = layout.camera_for_row(self.selection.y as usize);
+
+        if let Some(ref mut collections) = self.collections
+        {
+            for (row, collection) in collections.iter_mut().enumerate()
+            {
+                let heading = self.scroll_targets.get(&row).copied().unwrap_or(collection.scroll);
+                let scroll = navigation::scroll_for_focus(
+                    heading,
+                    focused_slot(collection, layout.columns, self.navigation.columns),
+                    layout.columns,
+                    collection.videos.len(),
+                    self.navigation.columns,
+                );
+
+                self.animator.cancel(Property::RowScroll(row));
+                collection.scroll = scroll;
+            }
+        }
+
+        self.scroll_targets.clear();
+        self.fling = None;
+        self.update_artwork();
+    }
+
+    /// Picks every tile again once the tile shape has changed noticeably or
+    /// tiles are drawn at another size.
+    fn update_artwork(&mut self)
+    {
+        let (aspect_ratio, width) = artwork_for(&self.tile_layout());
+
+        // Small changes keep the shape picked so far, a tile doesn't flip between two close ones
+        let aspect_ratio = match self.artwork
+        {
+            Some((picked, _)) if ((aspect_ratio - picked) / picked).abs() <= RESHAPE_THRESHOLD => picked,
+            _ => aspect_ratio,
+        };
+
+        if self.artwork != Some((aspect_ratio, width))
+        {
+            self.artwork = Some((aspect_ratio, width));
+
+            for collection in self.collections.iter_mut().flatten()
+            {
+                pick_tiles(collection, aspect_ratio, width);
+            }
+        }
+    }
+
+    /// What new rows pick their tiles for, the same as the rows already shown.
+    fn artwork(&mut self) -> (f32, u32)
+    {
+        let layout = self.tile_layout();
+        *self.artwork.get_or_insert_with(|| artwork_for(&layout))
+    }
+
+    /// Call once the artwork for `url` has been uploaded.
+    pub fn tile_uploaded(&mut self, url: &str)
+    {
+        for collection in self.collections.iter_mut().flatten()
+        {
+            for video in &mut collection.videos
+            {
+                video.tile_arrived(url);
+            }
+        }
+    }
+
+    /// Takes in rows sent by `get_collections`.
+    pub fn apply(&mut self, update: ContentUpdate)
+    {
+        let (aspect_ratio, width) = self.artwork();
+
+        match update
+        {
+            ContentUpdate::Home(mut home) =>
+            {
+                for collection in &mut home
+                {
+                    pick_tiles(collection, aspect_ratio, width);
+                    forget_previous_tiles(collection);
+                }
+
+                self.collections = Some(home);
+            }
+
+            ContentUpdate::Row(mut collection) =>
+            {
+                pick_tiles(&mut collection, aspect_ratio, width);
+                forget_previous_tiles(&mut collection);
+
+                if let Some(ref mut collections) = self.collections
+                {
+                    let index = collection.container_index;
+                    collections[index] = collection;
+                }
+            }
+        }
+    }
+
+    /// Takes in rows sent by `refresh_collections`, keeping the focus.
+    pub fn merge(&mut self, mut refreshed: Vec<Collection>)
+    {
+        let (aspect_ratio, width) = self.artwork();
+
+        for collection in &mut refreshed
+        {
+            pick_tiles(collection, aspect_ratio, width);
+            forget_previous_tiles(collection);
+        }
+
+        if let Some(ref mut collections) = self.collections
+        {
+            // Rows still scrolling finish their move at once, rows may change places
+            for (&row, &target) in &self.scroll_targets
+            {
+                if let Some(collection) = collections.get_mut(row)
+                {
+                    collection.scroll = target;
+                }
+            }
+
+            let focused_row = self.selection.y;
+            merge_collections(collections, refreshed, &mut self.selection);
+            self.animator.cancel_where(|property| matches!(property, Property::RowScroll(_)));
+            self.scroll_targets.clear();
+            self.fling = None;
+
+            if self.selection.y != focused_row
+            {
+                self.animator.cancel(Property::Camera);
+                self.camera.position = self.tile_layout().camera_for_row(self.selection.y as usize);
+            }
+        }
+    }
+
+    /// Advances the running animations by `dt` seconds, then applies `action`.
+    /// Moves redirect whatever move is still animating. Anything that has to
+    /// wait for an animation is kept for a moment and applied once it ends.
+    pub fn update(&mut self, action: Option<Action>, dt: f32) -> Option<Effect>
+    {
+        self.advance(dt);
+
+        let (action, waited) = match action
+        {
+            Some(action) => (action, 0.0),
+            None => self.buffered.map(|(action, waited)| (action, waited + dt))?,
+        };
+
+        self.buffered = None;
+
+        if waited > BUFFER_SECS
+        {
+            return None;
+        }
+
+        let browsing = self.showing_content.is_none();
+        let has_videos = selected_row_has_videos(&self.collections, self.selection);
+
+        match action
+        {
+            Action::MoveRight if browsing && has_videos => self.move_column(1),
+            Action::MoveLeft if browsing && has_videos => self.move_column(-1),
+            Action::MoveDown if browsing => self.move_row(1),
+            Action::MoveUp if browsing => self.move_row(-1),
+
+            // Content still opening or closing
+            Action::MoveRight | Action::MoveLeft | Action::MoveDown | Action::MoveUp
+                if self.animator.is_animating(Property::ContentSize) =>
+            {
+                self.buffered = Some((action, waited))
+            }
+
+            Action::Activate if self.load_error.is_some() =>
+            {
+                self.load_error = None;
+                return Some(Effect::Reload);
+            }
+
+            Action::Activate if browsing && has_videos && self.settled() => self.play(),
+            Action::Activate if browsing && has_videos => self.buffered = Some((action, waited)),
+
+            Action::Back if self.showing_content.is_some() => self.close_content(),
+
+            Action::Back | Action::Quit => self.running = false,
+
+            _ => (),
+        }
+
+        None
+    }
+
+    /// Hovering focuses a tile, clicking plays it or closes the playing content,
+    /// and dragging or the wheel scrolls a row.
+    pub fn pointer(&mut self, pointer: Pointer)
+    {
+        let position = match pointer
+        {
+            Pointer::Hover(position)
+            | Pointer::Press(position)
+            | Pointer::Drag(position)
+            | Pointer::Release(position) =>
+            {
+                self.pointer = Some(position);
+                position
+            }
+
+            Pointer::Wheel(_) => match self.pointer
+            {
+                Some(position) => position,
+                None => return,
+            },
+        };
+
+        // What is under the pointer, hit-tested against the same layout rows are drawn with
+        let layout = self.tile_layout();
+        let (tile, row) = {
+            let collections = self.collections.as_deref().unwrap_or_default();
+            let world = position + self.camera.position;
+            let repeating = self.navigation.columns == Edges::Carousel;
+            (layout.tile_at(world, collections, repeating), layout.row_at(world, collections.len()))
+        };
+
+        match pointer
+        {
+            Pointer::Hover(_) =>
+            {
+                if let Some((row, col)) = tile
+                {
+                    self.focus(row, col);
+                }
+            }
+
+            Pointer::Press(_) if self.showing_content.is_none() =>
+            {
+                if let Some(row) = row
+                {
+                    self.animator.cancel(Property::RowScroll(row));
+                    self.scroll_targets.remove(&row);
+                    self.drag =
+                        Some(Drag { row, start: position, last: position, moved: false, pending: 0.0, velocity: 0.0 });
+                    self.fling = None;
+                }
+            }
+
+            Pointer::Drag(_) =>
+            {
+                let Some(ref mut drag) = self.drag
+                else
+                {
+                    return;
+                };
+
+                let dx = if drag.moved
+                {
+                    position.x - drag.last.x
+                }
+                else if position.distance(drag.start) > DRAG_THRESHOLD
+                {
+                    drag.moved = true;
+                    position.x - drag.start.x
+                }
+                else
+                {
+                    0.0
+                };
+
+                drag.last = position;
+                let row = drag.row;
+                let scrolled = self.scroll_row(row, -dx / layout.col_width);
+
+                if let Some(ref mut drag) = self.drag
+                {
+                    drag.pending += scrolled;
+                }
+            }
+
+            Pointer::Release(_) if self.showing_content.is_some() => self.close_content(),
+
+            Pointer::Release(_) => match self.drag.take()
+            {
+                Some(drag) if drag.moved => self.fling = Some((drag.row, drag.velocity)),
+
+                Some(_) =>
+                {
+                    if let Some((row, col)) = tile
+                    {
+                        if self.focus(row, col)
+                        {
+                            self.play();
+                        }
+                    }
+                }
+
+                None => (),
+            },
+
+            Pointer::Wheel(steps) if self.showing_content.is_none() && self.drag.is_none() =>
+            {
+                let Some(row) = row
+                else
+                {
+                    return;
+                };
+
+                // A plain wheel only has vertical steps, rolling it down scrolls right
+                let steps = if steps.x != 0.0 { steps.x } else { -steps.y };
+                let velocity = match self.fling
+                {
+                    Some((flung, velocity)) if flung == row && velocity.signum() == steps.signum() => velocity,
+                    _ => 0.0,
+                };
+
+                self.fling = Some((row, velocity + steps * WHEEL_SPEED));
+            }
+
+            _ => (),
+        }
+    }
+
+    fn advance(&mut self, dt: f32)
+    {
+        if let Some(ref mut drag) = self.drag
+        {
+            if dt > 0.0
+            {
+                drag.velocity = (drag.velocity + drag.pending / dt) / 2.0;
+                drag.pending = 0.0;
+            }
+        }
+
+        if let Some((row, velocity)) = self.fling
+        {
+            let scrolled = self.scroll_row(row, velocity * dt);
+            let velocity = velocity * (-FLING_FRICTION * dt).exp();
+
+            // Stopping at either end rather than sliding along it
+            let blocked = dt > 0.0 && scrolled == 0.0;
+            self.fling = (velocity.abs() > FLING_STOP && !blocked).then_some((row, velocity));
+        }
+
+        // Out of the way while it runs so the setters and callbacks can have the whole state
+        let mut animator = std::mem::take(&mut self.animator);
+        animator.advance(dt, self);
+        let started = std::mem::replace(&mut self.animator, animator);
+        self.animator.absorb(started);
+    }
+
+    /// Nothing is on its way to a new position, so what is focused is what is seen.
+    fn settled(&self) -> bool
+    {
+        !self.animator.is_animating(Property::Camera)
+            && !self.animator.is_animating(Property::RowScroll(self.selection.y as usize))
+            && !self.animator.is_animating(Property::ContentSize)
+    }
+
+    /// Moves the focus along the row by `step`, scrolling the row once the
+    /// focus nears either side of the screen.
+    fn move_column(&mut self, step: i32)
+    {
+        let columns = self.tile_layout().columns;
+
+        let Some(ref mut collections) = self.collections
+        else
+        {
+            return;
+        };
+
+        let row = self.selection.y as usize;
+        let collection = &mut collections[row];
+        let count = collection.videos.len();
+        let edges = self.navigation.columns;
+
+        let Some(slot) = navigation::step(edges, focused_slot(collection, columns, edges), step, count as i32)
+        else
+        {
+            self.bump(glam::Vec2::X * step.signum() as f32);
+            return;
+        };
+
+        collection.selected_video = slot.rem_euclid(count as i32);
+        self.selection.x = collection.selected_video as f32;
+        self.travel = glam::Vec2::X * step.signum() as f32;
+
+        if self.fling.is_some_and(|(flung, _)| flung == row)
+        {
+            self.fling = None;
+        }
+
+        // Measured from where the row is headed, a move that doesn't change that leaves it going
+        let origin = collection.scroll;
+        let heading = self.scroll_targets.get(&row).copied().unwrap_or(origin);
+        let target = navigation::scroll_for_focus(heading, slot, columns, count, edges);
+
+        if target != heading
+        {
+            self.scroll_row_to(row, origin, target);
+        }
+
+        self.pop_focus();
+    }
+
+    /// Animates a row's scroll from `origin` to `target`, carrying on from the
+    /// move it was making if any.
+    fn scroll_row_to(&mut self, row: usize, origin: f32, target: f32)
+    {
+        let redirecting = self.animator.is_animating(Property::RowScroll(row));
+        self.animator.play(
+            Property::RowScroll(row),
+            glide(origin, target, redirecting),
+            move |state: &mut AppState, scroll| {
+                if let Some(collection) = state.collections.as_mut().and_then(|collections| collections.get_mut(row))
+                {
+                    collection.scroll = scroll;
+                }
+            },
+        );
+        self.animator.on_complete(Property::RowScroll(row), move |state: &mut AppState| {
+            state.scroll_targets.remove(&row);
+        });
+        self.scroll_targets.insert(row, target);
+    }
+
+    /// Moves the focus to another row by `step`. A row left while scrolling
+    /// keeps easing to where it was heading on its own.
+    fn move_row(&mut self, step: i32)
+    {
+        let Some(collections) = self.collections.as_ref().filter(|collections| !collections.is_empty())
+        else
+        {
+            return;
+        };
+
+        let edges = match self.navigation.rows
+        {
+            Edges::Carousel => Edges::Wrap,
+            edges => edges,
+        };
+
+        let Some(row) = navigation::step(edges, self.selection.y as i32, step, collections.len() as i32)
+        else
+        {
+            self.bump(glam::Vec2::Y * step.signum() as f32);
+            return;
+        };
+
+        self.selection.y = row as f32;
+        self.selection.x = collections[row as usize].selected_video as f32;
+        self.travel = glam::Vec2::Y * step.signum() as f32;
+        self.scroll_to_selected_row();
+        self.pop_focus();
+    }
+
+    /// Brings the focused row to the top of the screen.
+    fn scroll_to_selected_row(&mut self)
+    {
+        let origin = self.camera.position;
+        let target = self.tile_layout().camera_for_row(self.selection.y as usize);
+
+        let redirecting = self.animator.is_animating(Property::Camera);
+        self.animator.play(
+            Property::Camera,
+            glide(V2::from(origin), V2::from(target), redirecting),
+            |state: &mut AppState, position: V2| state.camera.position = position.into(),
+        );
+    }
+
+    /// Knocks the focused tile a little towards `direction` and back, for moves
+    /// stopped by a clamped edge.
+    fn bump(&mut self, direction: glam::Vec2)
+    {
+        let knock = V2::from(direction * 12.0);
+
+        #[rustfmt::skip]
+        self.animator.play(
+            Property::FocusNudge,
+            keyframes![
+                (V2(0.0, 0.0), 0.0, functions::EaseOut),
+                (knock, 0.08, functions::EaseInOut),
+                (V2(0.0, 0.0), 0.25, functions::EaseInOut)
+            ],
+            |state: &mut AppState, nudge: V2| state.focus_nudge = nudge.into(),
+        );
+    }
+
+    /// Grows the focused tile for a moment to draw the eye to it.
+    fn pop_focus(&mut self)
+    {
+        #[rustfmt::skip]
+        self.animator.play(
+            Property::FocusScale,
+            keyframes![
+                (1.0, 0.0, functions::EaseOut),
+                (1.06, 0.12, functions::EaseInOut),
+                (1.0, 0.3, functions::EaseInOut)
+            ],
+            |state: &mut AppState, scale| state.focus_scale = scale,
+        );
+    }
+
+    /// Moves the focus straight to a tile, for pointers. The row only scrolls
+    /// into view when it is partly off screen. Returns whether the tile could
+    /// be focused.
+    fn focus(&mut self, row: usize, col: usize) -> bool
+    {
+        let idle = self.showing_content.is_none() && self.settled();
+
+        let Some(ref mut collections) = self.collections
+        else
+        {
+            return false;
+        };
+
+        if !idle
+        {
+            return false;
+        }
+
+        if collections[row].selected_video == col as i32 && self.selection.y == row as f32
+        {
+            return true;
+        }
+
+        collections[row].selected_video = col as i32;
+        self.selection.x = col as f32;
+        self.pop_focus();
+
+        if row as f32 != self.selection.y
+        {
+            self.travel = glam::Vec2::Y * (row as f32 - self.selection.y).signum();
+            self.selection.y = row as f32;
+
+            let layout = self.tile_layout();
+            let top = layout.row_y(row) - self.camera.position.y;
+            let safe_area = layout.safe_area;
+
+            if top < safe_area.position.y || top + layout.row_height > safe_area.position.y + safe_area.size.y
+            {
+                self.scroll_to_selected_row();
+            }
+        }
+
+        true
+    }
+
+    /// Scrolls a row by up to `columns`, stopping at its first and last tile.
+    /// Returns how far it actually moved.
+    fn scroll_row(&mut self, row: usize, columns: f32) -> f32
+    {
+        let fitting = self.tile_layout().columns;
+
+        let Some(collection) = self.collections.as_mut().and_then(|collections| collections.get_mut(row))
+        else
+        {
+            return 0.0;
+        };
+
+        let scroll = match self.navigation.columns
+        {
+            Edges::Carousel => collection.scroll + columns,
+            _ =>
+            {
+                let last = navigation::max_scroll(fitting, collection.videos.len());
+                (collection.scroll + columns).clamp(0.0, last)
+            }
+        };
+        let scrolled = scroll - collection.scroll;
+
+        collection.scroll = scroll;
+        scrolled
+    }
+
+    /// Shrinks the content away once it has finished opening, unless it is
+    /// already on its way out.
+    fn close_content(&mut self)
+    {
+        if self.animator.is_animating(Property::ContentSize) && self.content_target == 0.0
+        {
+            return;
+        }
+
+        self.content_target = 0.0;
+
+        #[rustfmt::skip]
+        self.animator.queue(
+            Property::ContentSize,
+            keyframes![
+                (1.0, 0.0, functions::EaseInOut),
+                (0.0, 1.0, functions::EaseInOut)
+            ],
+            |state: &mut AppState, size| state.content_size = size,
+        );
+        self.animator.on_complete(Property::ContentSize, |state: &mut AppState| state.showing_content = None);
+    }
+
+    /// Opens the focused tile's content, assigning it a shader the first time.
+    fn play(&mut self)
+    {
+        let Some(ref mut collections) = self.collections
+        else
+        {
+            return;
+        };
+
+        let collection = &mut collections[self.selection.y as usize];
+        let content_index = &mut collection.videos[collection.selected_video as usize].content_index;
+
+        if *content_index == CONTENT_NOT_SET
+        {
+            *content_index = self.next_content;
+            self.next_content = (self.next_content + 1) % self.content_count.max(1);
+        }
+
+        self.showing_content = Some(*content_index);
+        self.content_target = 1.0;
+
+        #[rustfmt::skip]
+        self.animator.play(
+            Property::ContentSize,
+            keyframes![
+                (0.0, 0.0, functions::EaseInOut),
+                (1.0, 1.0, functions::EaseInOut)
+            ],
+            |state: &mut AppState, size| state.content_size = size,
+        );
+    }
+}
+
+/// The aspect ratio of the tiles and the width their artwork is requested at.
+fn artwork_for(layout: &TileLayout) -> (f32, u32)
+{
+    let tile = layout.tile_size;
+    (tile.x / tile.y, ((tile.x / ARTWORK_WIDTH_STEP).ceil() * ARTWORK_WIDTH_STEP) as u32)
+}
+
+fn pick_tiles(collection: &mut Collection, aspect_ratio: f32, width: u32)
+{
+    for video in &mut collection.videos
+    {
+        video.pick_tile(aspect_ratio, width);
+    }
+}
+
+/// Rows that were just loaded have nothing on screen to keep showing.
+fn forget_previous_tiles(collection: &mut Collection)
+{
+    for video in &mut collection.videos
+    {
+        video.previous_tile = None;
+    }
+}
+
+/// A move from `origin` to `target`. One that redirects a move in progress
+/// starts at full speed instead of easing in again, so held keys glide.
+fn glide<T: CanTween + Copy + Default>(origin: T, target: T, redirecting: bool) -> AnimationSequence<T>
+{
+    if redirecting
+    {
+        keyframes![(origin, 0.0, functions::EaseOut), (target, RETARGET_SECS, functions::EaseOut)]
+    }
+    else
+    {
+        keyframes![(origin, 0.0, functions::EaseInOut), (target, MOVE_SECS, functions::EaseInOut)]
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::content::collections::RowState;
+    use crate::test_support::row;
+
+    fn state(rows: Vec<Collection>) -> AppState
+    {
+        let mut state = AppState::new(glam::vec2(1024.0, 768.0), 3);
+        state.apply(ContentUpdate::Home(rows));
+        state
+    }
+
+    /// Long enough for any tween to finish and be dropped.
+    fn settle(state: &mut AppState)
+    {
+        for _ in 0 .. 3
+        {
+            state.update(None, 1.0);
+        }
+    }
+
+    #[test]
+    fn moves_wrap_around()
+    {
+        let mut state = state(vec![row("", 0 .. 3), row("", 0 .. 2)]);
+        state.navigation = Navigation { columns: Edges::Wrap, rows: Edges::Wrap };
+
+        state.update(Some(Action::MoveLeft), 0.0);
+        settle(&mut state);
+        assert_eq!(state.selection, glam::vec2(2.0, 0.0));
+        assert_eq!(state.travel, -glam::Vec2::X);
+
+        state.update(Some(Action::MoveUp), 0.0);
+        settle(&mut state);
+        assert_eq!(state.selection, glam::vec2(0.0, 1.0));
+        assert_eq!(state.camera.position, state.tile_layout().camera_for_row(1));
+
+        // Each row remembers its own focused tile
+        state.update(Some(Action::MoveDown), 0.0);
+        settle(&mut state);
+        assert_eq!(state.selection, glam::vec2(2.0, 0.0));
+
+        state.update(Some(Action::MoveRight), 0.0);
+        settle(&mut state);
+        assert_eq!(state.selection.x, 0.0);
+    }
+
+    #[test]
+    fn moves_redirect_the_running_tween()
+    {
+        let mut state = state(vec![row("", 0 .. 8), row("", 0 .. 3)]);
+
+        let scroll = |state: &AppState| state.collections.as_ref().unwrap()[0].scroll;
+
+        // Four tiles fit, the row only starts scrolling once the focus reaches the fourth
+        state.update(Some(Action::MoveRight), 0.0);
+        state.update(Some(Action::MoveRight), 0.0);
+        assert_eq!(scroll(&state), 0.0);
+        state.update(Some(Action::MoveRight), 0.0);
+        state.update(Some(Action::MoveRight), 0.1);
+        assert_eq!(state.selection.x, 4.0);
+
+        // The second move carries on from wherever the first one got to
+        let midway = scroll(&state);
+        state.update(None, 0.05);
+        assert!(scroll(&state) > midway && scroll(&state) < 2.0);
+
+        // Leaving the row lets it finish its move rather than jump, and it stays put
+        state.update(Some(Action::MoveDown), 0.05);
+        let left_at = scroll(&state);
+        state.update(None, 0.05);
+        assert!(scroll(&state) > left_at && scroll(&state) < 2.0);
+        settle(&mut state);
+        assert_eq!(state.selection, glam::vec2(0.0, 1.0));
+        assert_eq!(scroll(&state), 2.0);
+
+        state.update(Some(Action::MoveUp), 0.0);
+        settle(&mut state);
+        assert_eq!(state.selection, glam::vec2(4.0, 0.0));
+        assert_eq!(scroll(&state), 2.0);
+
+        // Activating mid-move waits for the move to end
+        state.update(Some(Action::MoveRight), 0.0);
+        state.update(Some(Action::Activate), 0.1);
+        assert!(state.showing_content.is_none());
+        for _ in 0 .. 10
+        {
+            state.update(None, 0.05);
+        }
+        assert_eq!(state.showing_content, Some(0));
+        assert_eq!(state.selection.x, 5.0);
+        assert_eq!(scroll(&state), 3.0);
+
+        // Rows without videos can't be moved along or played
+        let mut empty = row("", 0 .. 0);
+        empty.state = RowState::Loading;
+        let mut state = self::state(vec![empty]);
+
+        state.update(Some(Action::MoveRight), 0.0);
+        state.update(Some(Action::Activate), 0.0);
+        settle(&mut state);
+
+        assert_eq!(state.selection, glam::Vec2::ZERO);
+        assert!(state.showing_content.is_none());
+    }
+
+    #[test]
+    fn clamped_edges_bump_and_carousels_keep_going()
+    {
+        let mut state = state(vec![row("", 0 .. 5), row("", 0 .. 5)]);
+
+        // Clamped, the focus stays on the first tile and nudges against the edge
+        state.update(Some(Action::MoveLeft), 0.0);
+        state.update(None, 0.08);
+        assert_eq!(state.selection, glam::Vec2::ZERO);
+        assert!(state.focus_nudge.x < 0.0);
+        settle(&mut state);
+        assert_eq!(state.focus_nudge, glam::Vec2::ZERO);
+
+        state.update(Some(Action::MoveUp), 0.0);
+        settle(&mut state);
+        assert_eq!(state.selection, glam::Vec2::ZERO);
+
+        // A carousel carries on past the last tile into the next lap
+        state.navigation.columns = Edges::Carousel;
+        for _ in 0 .. 7
+        {
+            state.update(Some(Action::MoveRight), 0.0);
+            settle(&mut state);
+        }
+        let scroll = state.collections.as_ref().unwrap()[0].scroll;
+        assert_eq!(state.selection.x, 2.0);
+        assert_eq!(scroll, 5.0);
+        assert_eq!(focused_slot(&state.collections.as_ref().unwrap()[0], 4, Edges::Carousel), 7);
+    }
+
+    #[test]
+    fn refreshing_mid_scroll_finishes_the_move()
+    {
+        let rows = vec![row("a", 0 .. 8), row("b", 0 .. 8)];
+        let mut state = state(rows.clone());
+
+        for _ in 0 .. 4
+        {
+            state.update(Some(Action::MoveRight), 0.0);
+        }
+        state.update(None, 0.05);
+        let scroll = state.collections.as_ref().unwrap()[0].scroll;
+        assert!(scroll > 0.0 && scroll < 2.0);
+        state.fling = Some((1, 5.0));
+
+        // The rows swap places, the focused one lands where it was headed
+        let refreshed = rows
+            .into_iter()
+            .rev()
+            .enumerate()
+            .map(|(container_index, row)| Collection { container_index, ..row })
+            .collect();
+        state.merge(refreshed);
+
+        let collections = state.collections.as_ref().unwrap();
+        assert_eq!(state.selection, glam::vec2(4.0, 1.0));
+        assert_eq!((collections[1].set_id.as_str(), collections[1].scroll), ("a", 2.0));
+        assert_eq!(collections[0].scroll, 0.0);
+        assert!(state.fling.is_none());
+
+        settle(&mut state);
+        assert_eq!(state.collections.as_ref().unwrap()[1].scroll, 2.0);
+    }
+
+    #[test]
+    fn resizing_keeps_the_focused_row_and_tile_in_view()
+    {
+        let mut state = state(vec![row("", 0 .. 8), row("", 0 .. 8)]);
+
+        state.update(Some(Action::MoveDown), 0.0);
+        for _ in 0 .. 6
+        {
+            state.update(Some(Action::MoveRight), 0.0);
+        }
+        settle(&mut state);
+        assert_eq!(state.collections.as_ref().unwrap()[1].scroll, 4.0);
+
+        // Narrower, fewer tiles fit and the row scrolls further to keep the focus off the edge
+        state.resize(glam::vec2(700.0, 900.0));
+        let layout = state.tile_layout();
+        assert_eq!(layout.columns, 3);
+        assert_eq!(state.camera.position, layout.camera_for_row(1));
+        assert_eq!(state.collections.as_ref().unwrap()[1].scroll, 5.0);
+        assert_eq!(state.collections.as_ref().unwrap()[0].scroll, 0.0);
+    }
+
+    #[test]
+    fn resizing_picks_artwork_for_the_new_tile_shape()
+    {
+        let mut state = state(vec![row("", 0 .. 2)]);
+        let tile = |state: &AppState| state.collections.as_ref().unwrap()[0].videos[0].clone();
+
+        // Loaded rows get tiles of the drawn shape, requested about as wide as they are drawn
+        assert_eq!(tile(&state).tile.url, "http://localhost/0/wide?width=300");
+        assert!(tile(&state).previous_tile.is_none());
+
+        // Too short for 16:9 tiles, the flattened ones show banners instead
+        state.resize(glam::vec2(2560.0, 400.0));
+        assert_eq!(tile(&state).tile.url, "http://localhost/0/banner?width=400");
+        assert_eq!(tile(&state).previous_tile.unwrap().url, "http://localhost/0/wide?width=300");
+
+        // A slightly different shape keeps the artwork it has
+        state.resize(glam::vec2(2560.0, 410.0));
+        assert_eq!(tile(&state).tile.url, "http://localhost/0/banner?width=400");
+        assert_eq!(tile(&state).previous_tile.unwrap().url, "http://localhost/0/wide?width=300");
+
+        // Once the new artwork is up the old one isn't needed
+        state.tile_uploaded("http://localhost/0/banner?width=400");
+        assert!(tile(&state).previous_tile.is_none());
+    }
+
+    #[test]
+    fn resizing_again_keeps_the_artwork_on_screen()
+    {
+        let mut state = state(vec![row("", 0 .. 2)]);
+        let tile = |state: &AppState| state.collections.as_ref().unwrap()[0].videos[0].clone();
+
+        // Neither of the new tiles arrives before the next resize, the wide one stays up
+        state.resize(glam::vec2(2560.0, 400.0));
+        state.resize(glam::vec2(3840.0, 600.0));
+        assert!(tile(&state).tile.url.starts_with("http://localhost/0/banner"));
+        assert_ne!(tile(&state).tile.url, "http://localhost/0/banner?width=400");
+        assert_eq!(tile(&state).previous_tile.unwrap().url, "http://localhost/0/wide?width=300");
+
+        // Going back to the tile on screen needs nothing else
+        state.resize(glam::vec2(1024.0, 768.0));
+        assert_eq!(tile(&state).tile.url, "http://localhost/0/wide?width=300");
+        assert!(tile(&state).previous_tile.is_none());
+    }
+
+    #[test]
+    fn an_empty_home_ignores_every_action()
+    {
+        let mut state = state(Vec::new());
+        state.navigation = Navigation { columns: Edges::Wrap, rows: Edges::Wrap };
+
+        for action in [Action::MoveDown, Action::MoveUp, Action::MoveRight, Action::MoveLeft, Action::Activate]
+        {
+            state.update(Some(action), 0.0);
+        }
+        settle(&mut state);
+
+        assert_eq!(state.selection, glam::Vec2::ZERO);
+        assert!(state.showing_content.is_none());
+        assert!(state.running);
+    }
+
+    #[test]
+    fn content_opens_and_closes()
+    {
+        let mut state = state(vec![row("", 0 .. 2)]);
+
+        state.update(Some(Action::Activate), 0.0);
+        assert_eq!(state.showing_content, Some(0));
+
+        settle(&mut state);
+        assert_eq!(state.content_size, 1.0);
+
+        // Moving is ignored while content plays
+        state.update(Some(Action::MoveRight), 0.0);
+        settle(&mut state);
+        assert_eq!(state.selection.x, 0.0);
+
+        state.update(Some(Action::Back), 0.0);
+        assert!(state.running);
+        settle(&mut state);
+        assert!(state.showing_content.is_none());
+
+        // Another tile gets the next shader, the first one keeps its own
+        state.update(Some(Action::MoveRight), 0.0);
+        settle(&mut state);
+        state.update(Some(Action::Activate), 0.0);
+        assert_eq!(state.showing_content, Some(1));
+
+        state.update(Some(Action::Back), 0.0);
+        settle(&mut state);
+        state.update(Some(Action::Back), 0.0);
+        assert!(!state.running);
+    }
+
+    #[test]
+    fn closing_twice_closes_once()
+    {
+        let mut state = state(vec![row("", 0 .. 2)]);
+
+        state.update(Some(Action::Activate), 0.0);
+        settle(&mut state);
+
+        // A second Back or click while closing doesn't queue another close
+        state.update(Some(Action::Back), 0.0);
+        state.update(None, 0.5);
+        state.update(Some(Action::Back), 0.0);
+        state.pointer(Pointer::Release(glam::Vec2::ZERO));
+        state.update(None, 0.6);
+        assert!(state.showing_content.is_none());
+        assert_eq!(state.content_size, 0.0);
+
+        // So the next Activate plays straight away
+        state.update(Some(Action::Activate), 0.0);
+        assert_eq!(state.showing_content, Some(0));
+        assert!(state.running);
+    }
+
+    #[test]
+    fn activate_retries_after_a_load_error()
+    {
+        let mut state = AppState::new(glam::vec2(1024.0, 768.0), 3);
+        state.load_error = Some(String::from("HTTP 500"));
+
+        assert_eq!(state.update(Some(Action::Activate), 0.0), Some(Effect::Reload));
+        assert!(state.load_error.is_none());
+        assert_eq!(state.update(Some(Action::Activate), 0.0), None);
+
+        state.update(Some(Action::Quit), 0.0);
+        assert!(!state.running);
+    }
+
+    fn centre(state: &AppState, row: usize, slot: i32) -> glam::Vec2
+    {
+        let layout = state.tile_layout();
+        let scroll = state.collections.as_ref().unwrap()[row].scroll;
+        layout.tile_rect(row, slot, scroll).centre() - state.camera.position
+    }
+
+    #[test]
+    fn pointer_focuses_plays_and_closes()
+    {
+        let mut state = state(vec![row("", 0 .. 8), row("", 0 .. 8)]);
+
+        state.pointer(Pointer::Hover(centre(&state, 1, 2)));
+        assert_eq!(state.selection, glam::vec2(2.0, 1.0));
+        assert_eq!(state.camera.position, glam::Vec2::ZERO); // Already on screen
+
+        // A click plays the tile under it, another one anywhere closes it
+        let tile = centre(&state, 0, 3);
+        state.pointer(Pointer::Press(tile));
+        state.pointer(Pointer::Drag(tile + glam::vec2(2.0, 2.0)));
+        state.pointer(Pointer::Release(tile));
+        assert_eq!(state.selection, glam::vec2(3.0, 0.0));
+        assert_eq!(state.showing_content, Some(0));
+
+        settle(&mut state);
+        state.pointer(Pointer::Press(glam::Vec2::ZERO));
+        state.pointer(Pointer::Release(glam::Vec2::ZERO));
+        settle(&mut state);
+        assert!(state.showing_content.is_none());
+        assert!(state.running);
+    }
+
+    #[test]
+    fn dragged_rows_fling_and_stop_at_the_end()
+    {
+        let mut state = state(vec![row("", 0 .. 8), row("", 0 .. 8)]);
+        let col_width = state.tile_layout().col_width;
+        let start = centre(&state, 1, 1);
+
+        state.pointer(Pointer::Press(start));
+        for frame in 1 ..= 4
+        {
+            state.pointer(Pointer::Drag(start - glam::vec2(frame as f32 * col_width / 4.0, 0.0)));
+            state.update(None, 0.02);
+        }
+        state.pointer(Pointer::Release(start - glam::vec2(col_width, 0.0)));
+
+        // Dragging doesn't click or move the focus, the row keeps coasting afterwards
+        let scroll = |state: &AppState| state.collections.as_ref().unwrap()[1].scroll;
+        assert_eq!(scroll(&state), 1.0);
+        assert!(state.showing_content.is_none());
+        assert_eq!(state.selection, glam::Vec2::ZERO);
+
+        state.update(None, 0.1);
+        assert!(scroll(&state) > 1.0);
+
+        settle(&mut state);
+        assert_eq!(scroll(&state), 4.0);
+        assert!(state.fling.is_none());
+
+        // The wheel scrolls the row under the pointer back
+        state.pointer(Pointer::Hover(glam::vec2(1.0, centre(&state, 1, 0).y)));
+        state.pointer(Pointer::Wheel(glam::vec2(0.0, 2.0)));
+        settle(&mut state);
+        assert!(scroll(&state) < 4.0);
+        assert_eq!(state.collections.as_ref().unwrap()[0].scroll, 0.0);
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::content::model;
+use crate::ui::app::Action;
+
+/// Developer camera controls, only bound in debug builds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum DebugAction
+{
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding
+{
+    Action(Action),
+    Debug(DebugAction),
+}
+
+/// Which key triggers which action. Keys are named as SDL names them
+/// (`"Return"`, `"Backspace"`, `"A"`...) and game controller buttons as
+/// `"Pad "` followed by SDL's button name (`"Pad a"`, `"Pad dpup"`...). Names
+/// are compared case-insensitively, and any number of them can trigger the
+/// same action.
+///
+/// A bindings file lists keys per action, replacing the defaults for the
+/// actions it mentions and leaving the others alone:
+///
+/// ```json
+/// { "actions": { "Back": ["Escape", "Backspace", "B"] }, "debug": { "PanUp": ["I"] } }
+/// ```
+pub struct Bindings
+{
+    keys: HashMap<String, Binding>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BindingsFile
+{
+    #[serde(default)]
+    actions: HashMap<Action, Vec<String>>,
+    #[serde(default)]
+    debug: HashMap<DebugAction, Vec<String>>,
+}
+
+impl Bindings
+{
+    /// Arrows or the D-pad, Enter or A and Escape/Backspace or B, plus WASD
+    /// camera pans when `debug`.
+    pub fn defaults(debug: bool) -> Self
+    {
+        Self::with_overrides(BindingsFile::default(), debug)
+    }
+
+    /// The defaults with the bindings from a JSON file applied on top.
+    pub fn from_json(json: &str, debug: bool) -> Result<Self, model::ParseError>
+    {
+        Ok(Self::with_overrides(model::from_str(json)?, debug))
+    }
+
+    pub fn lookup(&self, key_name: &str) -> Option<Binding>
+    {
+        self.keys.get(&key_name.to_lowercase()).copied()
+    }
+
+    fn with_overrides(file: BindingsFile, debug: bool) -> Self
+    {
+        let mut actions = HashMap::from([
+            (Action::MoveLeft, keys(&["Left", "Pad dpleft"])),
+            (Action::MoveRight, keys(&["Right", "Pad dpright"])),
+            (Action::MoveUp, keys(&["Up", "Pad dpup"])),
+            (Action::MoveDown, keys(&["Down", "Pad dpdown"])),
+            (Action::Activate, keys(&["Return", "Keypad Enter", "Pad a"])),
+            (Action::Back, keys(&["Escape", "Backspace", "Pad b"])),
+        ]);
+
+        let mut debug_actions = HashMap::from([
+            (DebugAction::PanLeft, keys(&["A"])),
+            (DebugAction::PanRight, keys(&["D"])),
+            (DebugAction::PanUp, keys(&["W"])),
+            (DebugAction::PanDown, keys(&["S"])),
+        ]);
+
+        // The actions the file names lose their default keys
+        actions.retain(|action, _| !file.actions.contains_key(action));
+        debug_actions.retain(|action, _| !file.debug.contains_key(action));
+
+        let mut bindings = Self { keys: HashMap::new() };
+
+        // Defaults first so a key the file gives to another action always goes to
+        // that action. In each layer actions are bound last, so that a key claimed
+        // by both keeps working in release builds.
+        for (debug_actions, actions) in [(debug_actions, actions), (file.debug, file.actions)]
+        {
+            if debug
+            {
+                for (action, keys) in debug_actions
+                {
+                    bindings.bind(keys, Binding::Debug(action));
+                }
+            }
+
+            for (action, keys) in actions
+            {
+                bindings.bind(keys, Binding::Action(action));
+            }
+        }
+
+        bindings
+    }
+
+    fn bind(&mut self, keys: Vec<String>, binding: Binding)
+    {
+        for key in keys
+        {
+            self.keys.insert(key.to_lowercase(), binding);
+        }
+    }
+}
+
+/// How far a stick must be pushed, out of 1, to count as a direction.
+const STICK_DEAD_ZONE: f32 = 0.5;
+
+/// The wait before a held move starts repeating, in seconds.
+const FIRST_REPEAT: f32 = 0.4;
+
+/// Each repeat comes this much sooner than the previous one, down to
+/// `FASTEST_REPEAT`.
+const REPEAT_ACCELERATION: f32 = 0.8;
+const FASTEST_REPEAT: f32 = 0.08;
+
+/// Repeats a held move, faster the longer it is held.
+#[derive(Default)]
+struct Repeat
+{
+    held: Option<Action>,
+    interval: f32, // Until the next repeat after the one pending
+    until_repeat: f32,
+}
+
+impl Repeat
+{
+    fn start(&mut self, action: Option<Action>)
+    {
+        self.held = action;
+        self.interval = FIRST_REPEAT;
+        self.until_repeat = FIRST_REPEAT;
+    }
+
+    fn tick(&mut self, dt: f32) -> Option<Action>
+    {
+        self.until_repeat -= dt;
+
+        if self.held.is_none() || self.until_repeat > 0.0
+        {
+            return None;
+        }
+
+        self.interval = (self.interval * REPEAT_ACCELERATION).max(FASTEST_REPEAT);
+        self.until_repeat += self.interval;
+        self.held
+    }
+}
+
+/// Repeats moves while their key or button is held down. The press itself is
+/// handled as usual, only the repeats come from here, so the OS key repeat can
+/// be ignored.
+#[derive(Default)]
+pub struct KeyRepeat
+{
+    pressed: Vec<Action>, // Most recent last, it is the one repeating
+    repeat: Repeat,
+}
+
+impl KeyRepeat
+{
+    pub fn press(&mut self, action: Action)
+    {
+        if is_move(action) && !self.pressed.contains(&action)
+        {
+            self.pressed.push(action);
+            self.repeat.start(Some(action));
+        }
+    }
+
+    pub fn release(&mut self, action: Action)
+    {
+        if self.pressed.last() == Some(&action)
+        {
+            self.pressed.pop();
+            self.repeat.start(self.pressed.last().copied());
+        }
+        else
+        {
+            self.pressed.retain(|pressed| *pressed != action);
+        }
+    }
+
+    /// The repeat to make this frame, if any, `dt` seconds after the last call.
+    pub fn update(&mut self, dt: f32) -> Option<Action>
+    {
+        self.repeat.tick(dt)
+    }
+}
+
+/// Turns an analog stick into moves: one as soon as it is pushed in a
+/// direction, then repeats while it is held there.
+#[derive(Default)]
+pub struct StickRepeat
+{
+    position: glam::Vec2, // Both axes in -1 ..= 1, down is positive
+    repeat: Repeat,
+}
+
+impl StickRepeat
+{
+    pub fn set_x(&mut self, x: f32)
+    {
+        self.position.x = x;
+    }
+
+    pub fn set_y(&mut self, y: f32)
+    {
+        self.position.y = y;
+    }
+
+    /// The move to make this frame, if any, `dt` seconds after the last call.
+    pub fn update(&mut self, dt: f32) -> Option<Action>
+    {
+        let direction = self.direction();
+
+        if direction != self.repeat.held
+        {
+            self.repeat.start(direction);
+            return direction;
+        }
+
+        self.repeat.tick(dt)
+    }
+
+    /// The axis pushed furthest, if it is past the dead zone.
+    fn direction(&self) -> Option<Action>
+    {
+        let (x, y) = (self.position.x, self.position.y);
+
+        if x.abs().max(y.abs()) < STICK_DEAD_ZONE
+        {
+            None
+        }
+        else if x.abs() >= y.abs()
+        {
+            Some(if x > 0.0 { Action::MoveRight } else { Action::MoveLeft })
+        }
+        else
+        {
+            Some(if y > 0.0 { Action::MoveDown } else { Action::MoveUp })
+        }
+    }
+}
+
+fn is_move(action: Action) -> bool
+{
+    matches!(action, Action::MoveLeft | Action::MoveRight | Action::MoveUp | Action::MoveDown)
+}
+
+fn keys(names: &[&str]) -> Vec<String>
+{
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn defaults_bind_several_keys_per_action()
+    {
+        let bindings = Bindings::defaults(false);
+
+        assert_eq!(bindings.lookup("Escape"), Some(Binding::Action(Action::Back)));
+        assert_eq!(bindings.lookup("Backspace"), Some(Binding::Action(Action::Back)));
+        assert_eq!(bindings.lookup("return"), Some(Binding::Action(Action::Activate)));
+
+        // Debug pans only exist in debug mode
+        assert_eq!(bindings.lookup("W"), None);
+        assert_eq!(Bindings::defaults(true).lookup("W"), Some(Binding::Debug(DebugAction::PanUp)));
+    }
+
+    #[test]
+    fn file_replaces_only_the_actions_it_names()
+    {
+        let json = r#"{ "actions": { "MoveUp": ["W", "Up"], "Activate": ["Space"] } }"#;
+        let bindings = Bindings::from_json(json, true).unwrap();
+
+        assert_eq!(bindings.lookup("W"), Some(Binding::Action(Action::MoveUp)));
+        assert_eq!(bindings.lookup("Space"), Some(Binding::Action(Action::Activate)));
+        assert_eq!(bindings.lookup("Return"), None);
+        assert_eq!(bindings.lookup("Escape"), Some(Binding::Action(Action::Back)));
+        assert_eq!(bindings.lookup("A"), Some(Binding::Debug(DebugAction::PanLeft)));
+    }
+
+    #[test]
+    fn file_bindings_take_keys_from_default_actions()
+    {
+        // Whatever order the actions come in, the file wins
+        for _ in 0 .. 20
+        {
+            let bindings = Bindings::from_json(r#"{ "actions": { "Activate": ["Escape"] } }"#, true).unwrap();
+
+            assert_eq!(bindings.lookup("Escape"), Some(Binding::Action(Action::Activate)));
+            assert_eq!(bindings.lookup("Backspace"), Some(Binding::Action(Action::Back)));
+
+            let bindings = Bindings::from_json(r#"{ "debug": { "PanLeft": ["Left"] } }"#, true).unwrap();
+            assert_eq!(bindings.lookup("Left"), Some(Binding::Debug(DebugAction::PanLeft)));
+        }
+    }
+
+    #[test]
+    fn unknown_actions_are_rejected()
+    {
+        let error = Bindings::from_json(r#"{ "actions": { "Jump": ["Space"] } }"#, false).err().unwrap();
+
+        assert!(error.to_string().contains("unknown variant `Jump`"));
+    }
+
+    #[test]
+    fn held_stick_repeats_faster_and_faster()
+    {
+        let mut stick = StickRepeat::default();
+
+        stick.set_x(0.3);
+        assert_eq!(stick.update(0.0), None);
+
+        stick.set_x(0.9);
+        assert_eq!(stick.update(0.0), Some(Action::MoveRight));
+        assert_eq!(stick.update(0.3), None);
+
+        // Count the repeats over the next few seconds, the gaps between them must shrink
+        let mut repeats = Vec::new();
+        for frame in 1 ..= 300
+        {
+            if stick.update(0.01).is_some()
+            {
+                repeats.push(frame);
+            }
+        }
+
+        let gaps: Vec<_> = repeats.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        assert!(gaps.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert_eq!(*gaps.last().unwrap(), 8);
+
+        // Switching direction moves right away, letting go stops
+        stick.set_y(-1.0);
+        assert_eq!(stick.update(0.0), Some(Action::MoveUp));
+        stick.set_x(0.0);
+        stick.set_y(0.0);
+        assert_eq!(stick.update(0.0), None);
+        assert_eq!(stick.update(1.0), None);
+    }
+
+    #[test]
+    fn held_keys_repeat_the_latest_move()
+    {
+        let mut held = KeyRepeat::default();
+
+        held.press(Action::Activate);
+        held.press(Action::MoveRight);
+        assert_eq!(held.update(0.3), None);
+        assert_eq!(held.update(0.11), Some(Action::MoveRight));
+
+        // The newest key takes over, letting go of it goes back to the older one
+        held.press(Action::MoveDown);
+        assert_eq!(held.update(0.3), None);
+        assert_eq!(held.update(0.11), Some(Action::MoveDown));
+        held.release(Action::MoveDown);
+        assert_eq!(held.update(0.41), Some(Action::MoveRight));
+
+        held.release(Action::MoveRight);
+        assert_eq!(held.update(5.0), None);
+    }
+}
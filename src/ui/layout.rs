@@ -0,0 +1,371 @@
+use std::str::FromStr;
+
+use crate::content::collections::Collection;
+
+/// Sizes at a UI scale of 1, in pixels.
+const MARGIN: f32 = 16.0; // Inside the safe area, around the whole grid
+const GAP: f32 = 16.0; // Between tiles
+const ROW_GAP: f32 = 24.0; // Below each row's tiles
+const TITLE_SIZE: f32 = 32.0;
+const TITLE_GAP: f32 = 8.0; // Between a row's title and its tiles
+const BORDER: f32 = 4.0; // Around the focused tile
+
+/// Tiles flatten rather than let fewer rows than this fit on screen.
+const MIN_ROWS: f32 = 3.0;
+
+/// A rectangle, in world space unless said otherwise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect
+{
+    pub position: glam::Vec2, // Top left corner
+    pub size: glam::Vec2,
+}
+
+impl Rect
+{
+    pub fn contains(&self, point: glam::Vec2) -> bool
+    {
+        let offset = point - self.position;
+        offset.x >= 0.0 && offset.y >= 0.0 && offset.x < self.size.x && offset.y < self.size.y
+    }
+
+    pub fn centre(&self) -> glam::Vec2
+    {
+        self.position + self.size / 2.0
+    }
+
+    /// Grown by `by` on every side.
+    pub fn grown(&self, by: f32) -> Self
+    {
+        Self { position: self.position - glam::Vec2::splat(by), size: self.size + glam::Vec2::splat(by * 2.0) }
+    }
+
+    /// Scaled by `factor` around its centre.
+    pub fn scaled(&self, factor: f32) -> Self
+    {
+        let size = self.size * factor;
+        Self { position: self.centre() - size / 2.0, size }
+    }
+}
+
+/// From `min_width` up, in pixels at a UI scale of 1, rows show `tiles` tiles
+/// across the safe area. A fraction shows part of the next tile at the right
+/// edge, hinting that the row goes on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Breakpoint
+{
+    pub min_width: f32,
+    pub tiles: f32,
+}
+
+impl FromStr for Breakpoint
+{
+    type Err = String;
+
+    /// `WIDTH:TILES`, like `960:4.5`.
+    fn from_str(breakpoint: &str) -> Result<Self, Self::Err>
+    {
+        let invalid = || format!("expected WIDTH:TILES, not {:?}", breakpoint);
+        let (min_width, tiles) = breakpoint.split_once(':').ok_or_else(invalid)?;
+        let min_width: f32 = min_width.trim().parse().map_err(|_| invalid())?;
+        let tiles: f32 = tiles.trim().parse().map_err(|_| invalid())?;
+
+        match min_width >= 0.0 && tiles >= 1.0
+        {
+            true => Ok(Self { min_width, tiles }),
+            false => Err(invalid()),
+        }
+    }
+}
+
+/// Parts of the screen to keep clear, as fractions of the viewport. TVs can
+/// crop a few percent off each side (overscan).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Insets
+{
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl FromStr for Insets
+{
+    type Err = String;
+
+    /// Percentages, either one for every side or `TOP,RIGHT,BOTTOM,LEFT`.
+    fn from_str(insets: &str) -> Result<Self, Self::Err>
+    {
+        let invalid = || format!("expected PERCENT or TOP,RIGHT,BOTTOM,LEFT percentages, not {:?}", insets);
+        let sides = insets
+            .split(',')
+            .map(|side| side.trim().parse::<f32>().ok().filter(|side| (0.0 .. 50.0).contains(side)))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+
+        match sides[..]
+        {
+            [all] => Ok(Self { top: all / 100.0, right: all / 100.0, bottom: all / 100.0, left: all / 100.0 }),
+            [top, right, bottom, left] =>
+            {
+                Ok(Self { top: top / 100.0, right: right / 100.0, bottom: bottom / 100.0, left: left / 100.0 })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// What `TileLayout` is worked out from, besides the viewport.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayoutSettings
+{
+    pub breakpoints: Vec<Breakpoint>, // The widest one that fits applies
+    pub tile_aspect: f32,             // Width over height, unless the window is too short for it
+    pub safe_area: Insets,
+    pub scale: f32, // Pixels per pixel at a UI scale of 1, from the display DPI
+}
+
+impl Default for LayoutSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            breakpoints: vec![
+                Breakpoint { min_width: 0.0, tiles: 2.5 },
+                Breakpoint { min_width: 640.0, tiles: 3.5 },
+                Breakpoint { min_width: 960.0, tiles: 4.5 },
+                Breakpoint { min_width: 1600.0, tiles: 5.5 },
+                Breakpoint { min_width: 2560.0, tiles: 7.5 },
+            ],
+            tile_aspect: 16.0 / 9.0,
+            safe_area: Insets::default(),
+            scale: 1.0,
+        }
+    }
+}
+
+impl LayoutSettings
+{
+    /// Tiles across a safe area `width` pixels wide at a UI scale of 1.
+    pub fn tiles(&self, width: f32) -> f32
+    {
+        self.breakpoints
+            .iter()
+            .filter(|breakpoint| breakpoint.min_width <= width)
+            .max_by(|a, b| a.min_width.total_cmp(&b.min_width))
+            .or_else(|| self.breakpoints.first())
+            .map_or(1.0, |breakpoint| breakpoint.tiles.max(1.0))
+    }
+}
+
+/// Where rows and tiles sit in world space, the camera scrolls over them.
+/// Drawing and pointer hit-testing both go through it so they always agree.
+pub struct TileLayout
+{
+    pub scale: f32,         // Multiplies every fixed size, text included
+    pub safe_area: Rect,    // In screen space
+    pub origin: glam::Vec2, // Top left corner of the first row's title
+    pub title_size: f32,
+    pub title_height: f32, // Above each row's tiles
+    pub row_height: f32,   // Title, tiles and the gap below
+    pub col_width: f32,    // Tile and the gap beside it
+    pub tile_size: glam::Vec2,
+    pub border: f32,    // Around the focused tile
+    pub columns: usize, // Whole tiles that fit across the safe area
+}
+
+impl TileLayout
+{
+    pub fn new(viewport: glam::Vec2, settings: &LayoutSettings) -> Self
+    {
+        let scale = settings.scale.max(0.25);
+        let insets = settings.safe_area;
+        let safe_area = Rect {
+            position: viewport * glam::vec2(insets.left, insets.top),
+            size: viewport * glam::vec2(1.0 - insets.left - insets.right, 1.0 - insets.top - insets.bottom),
+        };
+
+        let margin = MARGIN * scale;
+        let gap = GAP * scale;
+        let width = (safe_area.size.x - margin * 2.0).max(1.0);
+        let col_width = (width + gap) / settings.tiles(safe_area.size.x / scale);
+
+        let title_size = TITLE_SIZE * scale;
+        let title_height = title_size + TITLE_GAP * scale;
+        let row_gap = ROW_GAP * scale;
+
+        // Short windows flatten the tiles to keep a few rows on screen
+        let tile_width = (col_width - gap).max(1.0);
+        let fitting = (safe_area.size.y - margin) / MIN_ROWS - title_height - row_gap;
+        let tile_height = (tile_width / settings.tile_aspect).min(fitting).max(1.0);
+        let tile_size = glam::vec2(tile_width, tile_height);
+
+        Self {
+            scale,
+            safe_area,
+            origin: safe_area.position + glam::Vec2::splat(margin),
+            title_size,
+            title_height,
+            row_height: title_height + tile_height + row_gap,
+            col_width,
+            tile_size,
+            border: BORDER * scale,
+            columns: ((width - tile_width) / col_width).max(0.0) as usize + 1,
+        }
+    }
+
+    /// Top of the row's title.
+    pub fn row_y(&self, row: usize) -> f32
+    {
+        row as f32 * self.row_height + self.origin.y
+    }
+
+    /// Where the camera sits with `row` at the top of the safe area.
+    pub fn camera_for_row(&self, row: usize) -> glam::Vec2
+    {
+        glam::Vec2::Y * row as f32 * self.row_height
+    }
+
+    pub fn title_position(&self, row: usize) -> glam::Vec2
+    {
+        glam::vec2(self.origin.x, self.row_y(row))
+    }
+
+    /// The tile in `slot` in a row scrolled by `scroll` columns. Slots are tile
+    /// indices, except in carousels where they go on past either end.
+    pub fn tile_rect(&self, row: usize, slot: i32, scroll: f32) -> Rect
+    {
+        let x = (slot as f32 - scroll) * self.col_width + self.origin.x;
+        Rect { position: glam::vec2(x, self.row_y(row) + self.title_height), size: self.tile_size }
+    }
+
+    /// Where a row without tiles shows its message, across the safe area.
+    pub fn banner_rect(&self, row: usize) -> Rect
+    {
+        let position = self.tile_rect(row, 0, 0.0).position;
+        let right = self.safe_area.position.x + self.safe_area.size.x - (self.origin.x - self.safe_area.position.x);
+        Rect { position, size: glam::vec2(right - position.x, self.tile_size.y) }
+    }
+
+    /// The row whose title or tiles are under `point`, in world space.
+    pub fn row_at(&self, point: glam::Vec2, rows: usize) -> Option<usize>
+    {
+        let row = ((point.y - self.origin.y) / self.row_height).floor();
+        (row >= 0.0 && (row as usize) < rows).then_some(row as usize)
+    }
+
+    /// The row and video index of the tile under `point`, in world space.
+    /// `repeating` rows are carousels, their tiles go on past either end.
+    pub fn tile_at(&self, point: glam::Vec2, collections: &[Collection], repeating: bool) -> Option<(usize, usize)>
+    {
+        let row = self.row_at(point, collections.len())?;
+        let count = collections[row].videos.len() as i32;
+        let scroll = collections[row].scroll;
+        let slot = ((point.x - self.origin.x) / self.col_width + scroll).floor() as i32;
+
+        match self.tile_rect(row, slot, scroll).contains(point) && count > 0
+        {
+            true if repeating => Some((row, slot.rem_euclid(count) as usize)),
+            true if (0 .. count).contains(&slot) => Some((row, slot as usize)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::test_support::row;
+
+    fn layout(width: f32, height: f32) -> TileLayout
+    {
+        TileLayout::new(glam::vec2(width, height), &LayoutSettings::default())
+    }
+
+    #[test]
+    fn hit_testing_finds_the_drawn_tiles()
+    {
+        let layout = layout(1200.0, 600.0);
+        let collections = [row("", 0 .. 10), Collection { scroll: 2.5, ..row("", 0 .. 10) }, row("", 0 .. 1)];
+
+        let centre = |row: usize, slot| layout.tile_rect(row, slot, collections[row].scroll).centre();
+
+        assert_eq!(layout.columns, 4);
+        assert_eq!(layout.tile_at(centre(0, 3), &collections, false), Some((0, 3)));
+        assert_eq!(layout.tile_at(centre(1, 4), &collections, false), Some((1, 4)));
+        assert_eq!(
+            layout.tile_at(glam::vec2(layout.origin.x + 1.0, centre(1, 0).y), &collections, false),
+            Some((1, 2))
+        );
+
+        // Titles, gaps between tiles and missing tiles are not hits
+        let title = layout.title_position(1) + glam::vec2(100.0, 1.0);
+        let tile = layout.tile_rect(0, 1, 0.0);
+        let gap = tile.position + glam::vec2(tile.size.x + 1.0, 1.0);
+        assert_eq!(layout.tile_at(title, &collections, false), None);
+        assert_eq!(layout.tile_at(gap, &collections, false), None);
+        assert_eq!(layout.tile_at(centre(2, 1), &collections, false), None);
+
+        // Unless the row is a carousel, where every slot holds a copy of some tile
+        assert_eq!(layout.tile_at(centre(2, 1), &collections, true), Some((2, 0)));
+        assert_eq!(layout.tile_at(centre(0, -3), &collections, true), Some((0, 7)));
+        assert_eq!(layout.row_at(title, collections.len()), Some(1));
+        assert_eq!(layout.row_at(layout.title_position(3) + glam::Vec2::ONE, collections.len()), None);
+    }
+
+    #[test]
+    fn breakpoints_pick_the_tiles_per_row()
+    {
+        // Every tile keeps its 16:9 shape, wider windows fit more of them
+        for (width, height, columns) in [(1024.0, 768.0, 4), (768.0, 1024.0, 3), (480.0, 854.0, 2), (3440.0, 1440.0, 7)]
+        {
+            let layout = layout(width, height);
+            assert_eq!(layout.columns, columns, "{}x{}", width, height);
+            assert!((layout.tile_size.x / layout.tile_size.y - 16.0 / 9.0).abs() < 0.001);
+
+            // The last whole tile ends inside the margin, the next one peeks past it
+            let last = layout.tile_rect(0, columns as i32 - 1, 0.0);
+            let next = layout.tile_rect(0, columns as i32, 0.0);
+            assert!(last.position.x + last.size.x <= width - MARGIN + 0.01);
+            assert!(next.position.x < width);
+        }
+
+        // Too short for 16:9 tiles, they flatten so three rows still fit
+        let layout = layout(2560.0, 600.0);
+        assert!(layout.tile_size.x / layout.tile_size.y > 2.0);
+        assert!(layout.row_y(3) < 600.0 + 0.01);
+
+        let settings = LayoutSettings {
+            breakpoints: vec!["0:2.5".parse().unwrap(), "800:5.5".parse().unwrap()],
+            ..Default::default()
+        };
+        assert_eq!(TileLayout::new(glam::vec2(799.0, 600.0), &settings).columns, 2);
+        assert_eq!(TileLayout::new(glam::vec2(800.0, 600.0), &settings).columns, 5);
+        assert!("4.5".parse::<Breakpoint>().is_err());
+    }
+
+    #[test]
+    fn scale_and_safe_area_shrink_the_usable_screen()
+    {
+        // A 4K screen at twice the scale looks like a 1080p one, only sharper
+        let hd = layout(1920.0, 1080.0);
+        let settings = LayoutSettings { scale: 2.0, ..Default::default() };
+        let uhd = TileLayout::new(glam::vec2(3840.0, 2160.0), &settings);
+        assert_eq!(uhd.columns, hd.columns);
+        assert_eq!(uhd.tile_size, hd.tile_size * 2.0);
+        assert_eq!(uhd.title_size, hd.title_size * 2.0);
+        assert_eq!(uhd.tile_rect(2, 3, 0.5).position, hd.tile_rect(2, 3, 0.5).position * 2.0);
+
+        // Overscan moves everything inside the safe area, which can be a smaller breakpoint
+        let settings = LayoutSettings { safe_area: "5".parse().unwrap(), ..Default::default() };
+        let tv = TileLayout::new(glam::vec2(1000.0, 800.0), &settings);
+        assert_eq!(tv.safe_area, Rect { position: glam::vec2(50.0, 40.0), size: glam::vec2(900.0, 720.0) });
+        assert_eq!(tv.title_position(0), glam::vec2(50.0 + MARGIN, 40.0 + MARGIN));
+        assert_eq!(tv.columns, 3);
+        assert_eq!(tv.banner_rect(1).position.x + tv.banner_rect(1).size.x, 950.0 - MARGIN);
+
+        assert_eq!("1,2,3,4".parse(), Ok(Insets { top: 0.01, right: 0.02, bottom: 0.03, left: 0.04 }));
+        assert!("1,2".parse::<Insets>().is_err());
+    }
+}
@@ -0,0 +1,8 @@
+//! Layout, navigation and animation state, independent of the renderer.
+
+pub mod animator;
+pub mod app;
+pub mod input;
+pub mod layout;
+pub mod navigation;
+pub mod tween;
@@ -0,0 +1,154 @@
+use std::str::FromStr;
+
+use crate::content::collections::Collection;
+
+/// What moving past the first or last tile of a row, or the first or last row,
+/// does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edges
+{
+    /// Jump to the other end.
+    Wrap,
+
+    /// Stay put with a little bump.
+    Clamp,
+
+    /// Keep going, the tiles repeat endlessly. Rows can't repeat, so for them
+    /// this is the same as `Wrap`.
+    Carousel,
+}
+
+impl FromStr for Edges
+{
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err>
+    {
+        match name
+        {
+            "wrap" => Ok(Self::Wrap),
+            "clamp" => Ok(Self::Clamp),
+            "carousel" => Ok(Self::Carousel),
+            _ => Err(format!("expected wrap, clamp or carousel, not {:?}", name)),
+        }
+    }
+}
+
+/// How the focus behaves at the edges, along each axis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Navigation
+{
+    pub columns: Edges,
+    pub rows: Edges,
+}
+
+impl Default for Navigation
+{
+    fn default() -> Self
+    {
+        Self { columns: Edges::Clamp, rows: Edges::Clamp }
+    }
+}
+
+/// Where the focus lands moving `step` from `from` among `count` items, `None`
+/// when a clamped edge is in the way. A carousel counts in slots, which go on
+/// past either end.
+pub fn step(edges: Edges, from: i32, step: i32, count: i32) -> Option<i32>
+{
+    let to = from + step;
+
+    match edges
+    {
+        Edges::Wrap => Some(to.rem_euclid(count)),
+        Edges::Clamp => (0 .. count).contains(&to).then_some(to),
+        Edges::Carousel => Some(to),
+    }
+}
+
+/// The scroll, in columns, that keeps the tile in slot `focus` on screen. Like
+/// streaming apps, the row only moves once the focus comes within a tile of
+/// either side of the `columns` that fit, and it never scrolls past its
+/// last tile unless it repeats.
+pub fn scroll_for_focus(scroll: f32, focus: i32, columns: usize, count: usize, edges: Edges) -> f32
+{
+    let columns = columns.max(1) as f32;
+    let lead = if columns >= 3.0 { 1.0 } else { 0.0 }; // Tiles kept in view past the focus
+    let focus = focus as f32;
+    let scroll = scroll.clamp(focus + lead + 1.0 - columns, focus - lead);
+
+    match edges
+    {
+        Edges::Carousel => scroll,
+        _ => scroll.clamp(0.0, max_scroll(columns as usize, count)),
+    }
+}
+
+/// How far a row can scroll with its last tile still against the right side.
+pub fn max_scroll(columns: usize, count: usize) -> f32
+{
+    count.saturating_sub(columns) as f32
+}
+
+/// The slot the focused tile is drawn in. Only a carousel repeats tiles, there
+/// it is the copy nearest the middle of the screen.
+pub fn focused_slot(collection: &Collection, columns: usize, edges: Edges) -> i32
+{
+    let count = collection.videos.len() as i32;
+
+    if edges != Edges::Carousel || count == 0
+    {
+        return collection.selected_video;
+    }
+
+    let middle = collection.scroll + (columns.max(1) - 1) as f32 / 2.0;
+    let laps = ((middle - collection.selected_video as f32) / count as f32).round() as i32;
+    collection.selected_video + laps * count
+}
+
+/// Rows that failed to load have no videos to move between or play.
+pub fn selected_row_has_videos(collections: &Option<Vec<Collection>>, selection: glam::Vec2) -> bool
+{
+    collections
+        .as_ref()
+        .and_then(|collections| collections.get(selection.y as usize))
+        .is_some_and(|collection| !collection.videos.is_empty())
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn row_scrolls_only_near_the_edges()
+    {
+        // Four tiles fit, the focus walks to the third one on screen and the row follows it from there
+        let scrolls: Vec<_> = (0 .. 8)
+            .scan(0.0, |scroll, focus| {
+                *scroll = scroll_for_focus(*scroll, focus, 4, 8, Edges::Clamp);
+                Some(*scroll)
+            })
+            .collect();
+        assert_eq!(scrolls, [0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 4.0, 4.0]);
+
+        // Going back only scrolls once the focus reaches the second tile on screen
+        assert_eq!(scroll_for_focus(4.0, 5, 4, 8, Edges::Clamp), 4.0);
+        assert_eq!(scroll_for_focus(4.0, 4, 4, 8, Edges::Clamp), 3.0);
+
+        // Short rows never scroll, carousels never stop
+        assert_eq!(scroll_for_focus(0.0, 2, 4, 3, Edges::Wrap), 0.0);
+        assert_eq!(scroll_for_focus(4.0, 9, 4, 8, Edges::Carousel), 7.0);
+        assert_eq!(scroll_for_focus(0.0, -1, 4, 8, Edges::Carousel), -2.0);
+    }
+
+    #[test]
+    fn steps_follow_the_edge_policy()
+    {
+        assert_eq!(step(Edges::Wrap, 2, 1, 3), Some(0));
+        assert_eq!(step(Edges::Clamp, 2, 1, 3), None);
+        assert_eq!(step(Edges::Clamp, 0, -1, 3), None);
+        assert_eq!(step(Edges::Carousel, 2, 1, 3), Some(3));
+        assert_eq!("carousel".parse(), Ok(Edges::Carousel));
+        assert!("bounce".parse::<Edges>().is_err());
+    }
+}
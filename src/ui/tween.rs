@@ -0,0 +1,21 @@
+use keyframe_derive::CanTween;
+
+/// A `glam::Vec2` that keyframe can tween.
+#[derive(Clone, Copy, Default, CanTween)]
+pub struct V2(pub f32, pub f32);
+
+impl From<V2> for glam::Vec2
+{
+    fn from(v2: V2) -> Self
+    {
+        glam::vec2(v2.0, v2.1)
+    }
+}
+
+impl From<glam::Vec2> for V2
+{
+    fn from(v2: glam::Vec2) -> Self
+    {
+        V2(v2.x, v2.y)
+    }
+}